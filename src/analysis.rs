@@ -0,0 +1,582 @@
+//! Kinematics and summary statistics derived from a trajectory.
+
+use std::collections::BTreeMap;
+
+use polars::prelude::*;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::error::TrajViewerError;
+
+/// Summary statistics reported by `--dry-run` and `--stats-out`.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub n_samples: usize,
+    pub duration: f64,
+    pub path_length: f64,
+    pub mean_speed: f64,
+    pub max_speed: f64,
+    pub n_turns: usize,
+    /// Cumulative absolute turning angle along the path, in radians.
+    pub total_turning: f64,
+    /// Net displacement over path length; 1.0 for a perfectly straight path.
+    pub straightness: f64,
+    /// Area of the convex hull of the (x, y) positions, a home-range-style
+    /// spatial-extent summary.
+    pub hull_area: f64,
+    pub period: Option<f64>,
+}
+
+impl Stats {
+    pub fn compute(xyz: &[(f64, f64, f64)], ts: &[f64]) -> Stats {
+        let speeds = speeds(xyz, ts);
+        let path_length: f64 = xyz
+            .windows(2)
+            .map(|w| dist(w[0], w[1]))
+            .sum();
+        let duration = match (ts.first(), ts.last()) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0.0,
+        };
+        let angles = turning_angles(xyz);
+        let n_turns = angles
+            .iter()
+            .filter(|a| **a > std::f64::consts::FRAC_PI_2)
+            .count();
+        let zs: Vec<f64> = xyz.iter().map(|p| p.2).collect();
+        Stats {
+            n_samples: xyz.len(),
+            duration,
+            path_length,
+            mean_speed: mean(&speeds),
+            max_speed: speeds.iter().cloned().fold(0.0, f64::max),
+            n_turns,
+            total_turning: angles.iter().sum(),
+            straightness: straightness(xyz),
+            hull_area: polygon_area(&convex_hull(
+                &xyz.iter().map(|p| (p.0, p.1)).collect::<Vec<_>>(),
+            )),
+            period: get_period(ts, &zs),
+        }
+    }
+}
+
+/// Straightness index of the path: net displacement divided by path
+/// length. 1.0 for a straight line, approaching 0 for a tangled path;
+/// defined as 1.0 for paths too short to bend.
+pub fn straightness(xyz: &[(f64, f64, f64)]) -> f64 {
+    if xyz.len() < 2 {
+        return 1.0;
+    }
+    let path_length: f64 = xyz.windows(2).map(|w| dist(w[0], w[1])).sum();
+    if path_length <= 0.0 {
+        return 1.0;
+    }
+    dist(xyz[0], xyz[xyz.len() - 1]) / path_length
+}
+
+/// 2D convex hull of `points` via Andrew's monotone chain, returned in
+/// counter-clockwise order without the closing point. Degenerate inputs
+/// (fewer than three distinct points) come back as-is, sorted.
+pub fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut pts: Vec<(f64, f64)> = points
+        .iter()
+        .copied()
+        .filter(|p| p.0.is_finite() && p.1.is_finite())
+        .collect();
+    pts.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    pts.dedup();
+    if pts.len() < 3 {
+        return pts;
+    }
+
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(pts.len() * 2);
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Box<dyn Iterator<Item = &(f64, f64)>> = if pass == 0 {
+            Box::new(pts.iter())
+        } else {
+            Box::new(pts.iter().rev())
+        };
+        for &p in iter {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+/// Area of a simple polygon given its vertices in order (shoelace
+/// formula); 0.0 for degenerate polygons.
+pub fn polygon_area(vertices: &[(f64, f64)]) -> f64 {
+    if vertices.len() < 3 {
+        return 0.0;
+    }
+    let mut twice_area = 0.0;
+    for i in 0..vertices.len() {
+        let (x0, y0) = vertices[i];
+        let (x1, y1) = vertices[(i + 1) % vertices.len()];
+        twice_area += x0 * y1 - x1 * y0;
+    }
+    (twice_area / 2.0).abs()
+}
+
+/// Data-quality metrics computed by `--qc` on the raw (pre-fill) columns.
+#[derive(Debug, Serialize)]
+pub struct QcReport {
+    pub n_samples: usize,
+    /// Fraction of null or NaN values per column.
+    pub null_fraction: BTreeMap<String, f64>,
+    /// Number of samples whose `t` is smaller than the previous one.
+    pub n_nonmonotonic_t: usize,
+    /// Samples outside `--fixed-bounds`, when fixed bounds are given.
+    pub n_out_of_bounds: Option<usize>,
+    pub median_dt: f64,
+    pub stddev_dt: f64,
+    pub duration: f64,
+}
+
+impl QcReport {
+    /// Compute the report from the raw trajectory columns, before any
+    /// null-filling so data gaps are still visible.
+    pub fn compute(df: &DataFrame, config: &Config) -> Result<QcReport, TrajViewerError> {
+        let n = df.height();
+
+        let mut null_fraction = BTreeMap::new();
+        for series in df.get_columns() {
+            let floats = series.cast(&DataType::Float64)?;
+            let bad = floats
+                .f64()?
+                .into_iter()
+                .filter(|v| v.is_none_or(f64::is_nan))
+                .count();
+            null_fraction.insert(series.name().to_string(), bad as f64 / n.max(1) as f64);
+        }
+
+        let ts: Vec<f64> = df
+            .column("t")?
+            .cast(&DataType::Float64)?
+            .f64()?
+            .into_iter()
+            .flatten()
+            .collect();
+        let n_nonmonotonic_t = ts.windows(2).filter(|w| w[1] < w[0]).count();
+        let mut dts: Vec<f64> = ts.windows(2).map(|w| w[1] - w[0]).collect();
+        dts.sort_by(|a, b| a.total_cmp(b));
+        let median_dt = if dts.is_empty() {
+            0.0
+        } else {
+            dts[dts.len() / 2]
+        };
+        let dt_mean = mean(&dts);
+        let stddev_dt = if dts.is_empty() {
+            0.0
+        } else {
+            (dts.iter().map(|d| (d - dt_mean).powi(2)).sum::<f64>() / dts.len() as f64).sqrt()
+        };
+        let duration = match (ts.first(), ts.last()) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0.0,
+        };
+
+        let n_out_of_bounds = match &config.fixed_bounds {
+            Some(b) => {
+                let mut count = 0;
+                for (name, (lo, hi)) in [("x", (b[0], b[1])), ("y", (b[2], b[3])), ("z", (b[4], b[5]))] {
+                    count += df
+                        .column(name)?
+                        .cast(&DataType::Float64)?
+                        .f64()?
+                        .into_iter()
+                        .flatten()
+                        .filter(|v| *v < lo || *v > hi)
+                        .count();
+                }
+                Some(count)
+            }
+            None => None,
+        };
+
+        Ok(QcReport {
+            n_samples: n,
+            null_fraction,
+            n_nonmonotonic_t,
+            n_out_of_bounds,
+            median_dt,
+            stddev_dt,
+            duration,
+        })
+    }
+
+    /// Print the report as a small human-readable table.
+    pub fn print(&self) {
+        println!("QC report");
+        println!("  samples            {}", self.n_samples);
+        for (name, fraction) in &self.null_fraction {
+            println!("  null/NaN {name}         {:.2}%", fraction * 100.0);
+        }
+        println!("  non-monotonic t    {}", self.n_nonmonotonic_t);
+        if let Some(oob) = self.n_out_of_bounds {
+            println!("  out of bounds      {oob}");
+        }
+        println!("  median delta-t     {:.4}", self.median_dt);
+        println!("  stddev delta-t     {:.4}", self.stddev_dt);
+        println!("  duration           {:.2}", self.duration);
+    }
+}
+
+/// Temporal-structure summary printed by `--timeline`: where the
+/// recording starts and ends, how much of it is lost to gaps, and how
+/// complete the sampling is against the median rate.
+#[derive(Debug, Serialize)]
+pub struct TimelineReport {
+    pub n_samples: usize,
+    pub t_start: f64,
+    pub t_end: f64,
+    pub duration: f64,
+    pub median_dt: f64,
+    /// Threshold a delta-t must exceed to count as a gap: `--gap-threshold`
+    /// when given, otherwise three times the median delta-t.
+    pub gap_threshold: f64,
+    pub n_gaps: usize,
+    /// Total data time covered by gaps.
+    pub gap_total: f64,
+    /// Fraction of the samples the median rate predicts that are present.
+    pub coverage: f64,
+}
+
+impl TimelineReport {
+    pub fn compute(ts: &[f64], config: &Config) -> TimelineReport {
+        let n = ts.len();
+        let (t_start, t_end) = match (ts.first(), ts.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => (0.0, 0.0),
+        };
+        let duration = t_end - t_start;
+
+        let mut dts: Vec<f64> = ts.windows(2).map(|w| w[1] - w[0]).collect();
+        dts.sort_by(|a, b| a.total_cmp(b));
+        let median_dt = if dts.is_empty() {
+            0.0
+        } else {
+            dts[dts.len() / 2]
+        };
+        let gap_threshold = config.gap_threshold.unwrap_or(3.0 * median_dt);
+        let gaps: Vec<f64> = dts
+            .iter()
+            .copied()
+            .filter(|dt| gap_threshold > 0.0 && *dt > gap_threshold)
+            .collect();
+        // `fold`, not `sum`: an empty f64 `sum()` is -0.0, which would
+        // print as "-0.00" for gap-free recordings.
+        let gap_total: f64 = gaps.iter().fold(0.0, |acc, gap| acc + gap);
+
+        // Samples the median rate predicts over the full span, counting
+        // both endpoints; 1.0 means no sample is missing.
+        let coverage = if median_dt > 0.0 && duration > 0.0 {
+            (n as f64 / (duration / median_dt + 1.0)).min(1.0)
+        } else {
+            1.0
+        };
+
+        TimelineReport {
+            n_samples: n,
+            t_start,
+            t_end,
+            duration,
+            median_dt,
+            gap_threshold,
+            n_gaps: gaps.len(),
+            gap_total,
+            coverage,
+        }
+    }
+
+    /// Print the report as a small human-readable table.
+    pub fn print(&self) {
+        println!("Timeline report");
+        println!("  samples            {}", self.n_samples);
+        println!("  t start            {:.4}", self.t_start);
+        println!("  t end              {:.4}", self.t_end);
+        println!("  duration           {:.2}", self.duration);
+        println!("  median delta-t     {:.4}", self.median_dt);
+        println!(
+            "  gaps > {:.4}      {} ({:.2} total)",
+            self.gap_threshold, self.n_gaps, self.gap_total
+        );
+        println!("  coverage           {:.1}%", self.coverage * 100.0);
+    }
+}
+
+/// Instantaneous speed per sample (first sample repeats the second so the
+/// result has the same length as the input).
+pub fn speeds(xyz: &[(f64, f64, f64)], ts: &[f64]) -> Vec<f64> {
+    if xyz.len() < 2 {
+        return vec![0.0; xyz.len()];
+    }
+    let mut out = Vec::with_capacity(xyz.len());
+    out.push(0.0);
+    for i in 1..xyz.len() {
+        let dt = ts[i] - ts[i - 1];
+        if dt > 0.0 {
+            out.push(dist(xyz[i - 1], xyz[i]) / dt);
+        } else {
+            out.push(out[i - 1]);
+        }
+    }
+    out[0] = out[1];
+    out
+}
+
+/// Acceleration magnitude per sample: the change of the velocity vector
+/// between adjacent samples over `dt` (padded at the ends like [`speeds`]).
+pub fn accels(xyz: &[(f64, f64, f64)], ts: &[f64]) -> Vec<f64> {
+    if xyz.len() < 3 {
+        return vec![0.0; xyz.len()];
+    }
+    let mut velocities = Vec::with_capacity(xyz.len());
+    velocities.push((0.0, 0.0, 0.0));
+    for i in 1..xyz.len() {
+        let dt = (ts[i] - ts[i - 1]).max(f64::EPSILON);
+        velocities.push((
+            (xyz[i].0 - xyz[i - 1].0) / dt,
+            (xyz[i].1 - xyz[i - 1].1) / dt,
+            (xyz[i].2 - xyz[i - 1].2) / dt,
+        ));
+    }
+    velocities[0] = velocities[1];
+
+    let mut out = Vec::with_capacity(xyz.len());
+    out.push(0.0);
+    for i in 1..velocities.len() {
+        let dt = (ts[i] - ts[i - 1]).max(f64::EPSILON);
+        let (u, v) = (velocities[i - 1], velocities[i]);
+        let d = (v.0 - u.0, v.1 - u.1, v.2 - u.2);
+        out.push((d.0 * d.0 + d.1 * d.1 + d.2 * d.2).sqrt() / dt);
+    }
+    out[0] = out[1];
+    out
+}
+
+/// Unsigned turning angle (radians) at each interior sample, computed from
+/// the two adjacent displacement vectors.
+pub fn turning_angles(xyz: &[(f64, f64, f64)]) -> Vec<f64> {
+    let mut out = Vec::new();
+    for w in xyz.windows(3) {
+        let u = (w[1].0 - w[0].0, w[1].1 - w[0].1, w[1].2 - w[0].2);
+        let v = (w[2].0 - w[1].0, w[2].1 - w[1].1, w[2].2 - w[1].2);
+        let nu = (u.0 * u.0 + u.1 * u.1 + u.2 * u.2).sqrt();
+        let nv = (v.0 * v.0 + v.1 * v.1 + v.2 * v.2).sqrt();
+        if nu == 0.0 || nv == 0.0 {
+            out.push(0.0);
+            continue;
+        }
+        let cos = ((u.0 * v.0 + u.1 * v.1 + u.2 * v.2) / (nu * nv)).clamp(-1.0, 1.0);
+        out.push(cos.acos());
+    }
+    out
+}
+
+/// Dominant oscillation period of `values` in the units of `ts`, estimated
+/// from the first autocorrelation peak. Returns `None` when no clear
+/// periodicity is found.
+pub fn get_period(ts: &[f64], values: &[f64]) -> Option<f64> {
+    let n = values.len();
+    if n < 8 {
+        return None;
+    }
+    let m = mean(values);
+    let centered: Vec<f64> = values.iter().map(|v| v - m).collect();
+    let denom: f64 = centered.iter().map(|v| v * v).sum();
+    if denom == 0.0 {
+        return None;
+    }
+
+    // Autocorrelation up to half the series length; pick the first local
+    // maximum after the zero-lag peak.
+    let max_lag = n / 2;
+    let mut acf = Vec::with_capacity(max_lag);
+    for lag in 1..max_lag {
+        let mut sum = 0.0;
+        for i in lag..n {
+            sum += centered[i] * centered[i - lag];
+        }
+        acf.push(sum / denom);
+    }
+
+    let mut best: Option<(usize, f64)> = None;
+    for lag in 1..acf.len().saturating_sub(1) {
+        if acf[lag] > acf[lag - 1]
+            && acf[lag] >= acf[lag + 1]
+            && acf[lag] > 0.2
+            && best.is_none_or(|(_, v)| acf[lag] > v)
+        {
+            best = Some((lag + 1, acf[lag]));
+        }
+    }
+
+    let (lag, _) = best?;
+    let dt = (ts[n - 1] - ts[0]) / (n - 1) as f64;
+    Some(lag as f64 * dt)
+}
+
+/// Dominant period and frequency of the `z` oscillation in sliding
+/// windows of `window` samples advanced by `step`, as a DataFrame with
+/// `t_center`, `period` and `frequency` columns (null where no clear
+/// periodicity is found). Captures frequency drift over a trial that the
+/// single global [`get_period`] estimate averages away.
+pub fn windowed_period(df: &DataFrame, window: usize, step: usize) -> PolarsResult<DataFrame> {
+    let data = df
+        .select(["z", "t"])?
+        .to_ndarray::<Float64Type>(IndexOrder::C)?;
+    let zs: Vec<f64> = data.column(0).to_vec();
+    let ts: Vec<f64> = data.column(1).to_vec();
+
+    let window = window.max(8);
+    let step = step.max(1);
+    let mut centers = Vec::new();
+    let mut periods: Vec<Option<f64>> = Vec::new();
+    let mut start = 0;
+    while start + window <= zs.len() {
+        let end = start + window;
+        centers.push((ts[start] + ts[end - 1]) / 2.0);
+        periods.push(get_period(&ts[start..end], &zs[start..end]));
+        start += step;
+    }
+    let frequencies: Vec<Option<f64>> = periods
+        .iter()
+        .map(|p| p.map(|p| 1.0 / p.max(f64::EPSILON)))
+        .collect();
+
+    df!(
+        "t_center" => centers,
+        "period" => periods,
+        "frequency" => frequencies,
+    )
+}
+
+/// Centered moving average with the window clipped at the edges.
+pub fn moving_average(xs: &[f64], window: usize) -> Vec<f64> {
+    if window <= 1 || xs.is_empty() {
+        return xs.to_vec();
+    }
+    let half = window / 2;
+    (0..xs.len())
+        .map(|i| {
+            let lo = i.saturating_sub(half);
+            let hi = (i + half + 1).min(xs.len());
+            xs[lo..hi].iter().sum::<f64>() / (hi - lo) as f64
+        })
+        .collect()
+}
+
+/// Value at quantile `q` (0 to 1) of `xs`, by nearest-rank on a sorted copy.
+pub fn percentile(xs: &[f64], q: f64) -> f64 {
+    if xs.is_empty() {
+        return 0.0;
+    }
+    let mut sorted: Vec<f64> = xs.iter().copied().filter(|v| v.is_finite()).collect();
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let idx = (q.clamp(0.0, 1.0) * (sorted.len() - 1) as f64).round() as usize;
+    sorted[idx]
+}
+
+fn dist(a: (f64, f64, f64), b: (f64, f64, f64)) -> f64 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2) + (b.2 - a.2).powi(2)).sqrt()
+}
+
+fn mean(xs: &[f64]) -> f64 {
+    if xs.is_empty() {
+        0.0
+    } else {
+        xs.iter().sum::<f64>() / xs.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hull_of_square_with_interior_point() {
+        let points = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (0.0, 1.0),
+            (0.5, 0.5),
+        ];
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 4);
+        assert!(!hull.contains(&(0.5, 0.5)));
+        assert!((polygon_area(&hull) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn timeline_counts_gaps_and_coverage() {
+        use clap::Parser;
+
+        let config = Config::parse_from(["traj_viewer", "traj"]);
+        let ts = [0.0, 0.1, 0.2, 1.0, 1.1, 1.2];
+        let report = TimelineReport::compute(&ts, &config);
+        // Median delta-t is 0.1, so the default threshold of three median
+        // steps flags only the 0.2 -> 1.0 jump.
+        assert_eq!(report.n_gaps, 1);
+        assert!((report.gap_total - 0.8).abs() < 1e-9);
+        assert!((report.duration - 1.2).abs() < 1e-9);
+        // 6 of the 13 samples the median rate predicts are present.
+        assert!((report.coverage - 6.0 / 13.0).abs() < 1e-9);
+
+        let config = Config::parse_from(["traj_viewer", "traj", "--gap-threshold", "2.0"]);
+        assert_eq!(TimelineReport::compute(&ts, &config).n_gaps, 0);
+    }
+
+    #[test]
+    fn windowed_period_tracks_a_sine() {
+        let ts: Vec<f64> = (0..400).map(|i| i as f64 * 0.05).collect();
+        let zs: Vec<f64> = ts
+            .iter()
+            .map(|t| (2.0 * std::f64::consts::PI * t / 4.0).sin())
+            .collect();
+        let df = df!("z" => zs, "t" => ts).unwrap();
+
+        let out = windowed_period(&df, 200, 50).unwrap();
+        assert!(out.height() >= 3);
+        let period = out.column("period").unwrap().f64().unwrap();
+        for v in period.into_no_null_iter() {
+            assert!((v - 4.0).abs() < 0.5, "period was {v}");
+        }
+    }
+
+    #[test]
+    fn period_of_sine() {
+        let ts: Vec<f64> = (0..200).map(|i| i as f64 * 0.1).collect();
+        let zs: Vec<f64> = ts.iter().map(|t| (t * std::f64::consts::TAU / 4.0).sin()).collect();
+        let period = get_period(&ts, &zs).expect("period detected");
+        assert!((period - 4.0).abs() < 0.3, "period was {period}");
+    }
+
+    #[test]
+    fn straight_line_has_no_turns() {
+        let xyz: Vec<(f64, f64, f64)> = (0..10).map(|i| (i as f64, 0.0, 0.0)).collect();
+        assert!(turning_angles(&xyz).iter().all(|a| *a < 1e-9));
+    }
+
+    #[test]
+    fn moving_average_preserves_length() {
+        let xs = vec![1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(moving_average(&xs, 3).len(), xs.len());
+        assert_eq!(moving_average(&xs, 1), xs);
+    }
+}
@@ -0,0 +1,866 @@
+//! Command line configuration.
+
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+
+/// How log lines are formatted.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// Human-readable text (default).
+    Plain,
+    /// One JSON object per event on stderr, for log scrapers.
+    Json,
+}
+
+/// How trajectory time origins are aligned before rendering.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlignTime {
+    /// Keep timestamps as loaded (default).
+    None,
+    /// Subtract each trajectory's first `t`, so all start at zero.
+    Start,
+    /// Subtract the `event_time` from each trajectory's `.meta.json`.
+    Event,
+}
+
+/// How the body trail is drawn.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenderStyle {
+    /// A thin polyline (default).
+    Line,
+    /// Depth-shaded quads giving the path apparent thickness.
+    Tube,
+}
+
+/// Corner a chart decoration is anchored to.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Overall color scheme of the rendered frames.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// White background with dark ink (default).
+    Light,
+    /// Near-black background with light ink, for screen viewing.
+    Dark,
+}
+
+/// Where the chart caption is drawn.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPos {
+    /// Top-center, the plotters default.
+    Top,
+    /// Bottom-center, clear of timelines and top annotations.
+    Bottom,
+    /// No caption at all.
+    None,
+}
+
+/// Where the plane projections are drawn.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectionLayout {
+    /// Onto the walls of the 3D box (default).
+    OnBox,
+    /// As separate small 2D charts below the 3D view.
+    Panels,
+}
+
+/// Named `--projections` subsets, so common figures don't require
+/// remembering which plane letters land on which wall.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectionPreset {
+    /// Only the ground-plane shadow.
+    FloorOnly,
+    /// The ground-plane shadow plus the back wall.
+    FloorAndBack,
+    /// All three planes (same as the `--projections` default).
+    All,
+}
+
+/// Gradient used wherever a scalar is mapped to color (time, speed,
+/// acceleration, density).
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Colormap {
+    /// Perceptually uniform dark-purple-to-yellow (default).
+    Viridis,
+    /// Perceptually uniform blue-to-yellow through magenta.
+    Plasma,
+    /// High-contrast rainbow, perceptually smoothed.
+    Turbo,
+    /// Classic blue-cyan-yellow-red rainbow.
+    Jet,
+    /// Black to white.
+    Grayscale,
+}
+
+/// Output mode of the renderer.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    /// Animated GIF (default).
+    Gif,
+    /// Like gif, but the drawn path grows from the first sample and never
+    /// shrinks, so each frame shows the entire history so far.
+    Accumulate,
+    /// Numbered PNG files, one per frame.
+    PngSequence,
+    /// Static 2D occupancy heatmap of the XY positions.
+    Heatmap,
+    /// Rotating GIF of the trajectory voxelized into a 3D density grid.
+    Density3d,
+}
+
+/// 3D trajectory viewer - renders animated GIFs of tracked 3D trajectories.
+///
+/// Reads a CSV (or Parquet) file with `x`, `y`, `z`, `t` columns from a local
+/// directory or an S3 bucket and renders the trajectory as a rotating 3D
+/// animation with optional wall projections.
+#[derive(Parser, Debug, Clone, Serialize)]
+#[command(author, version, about)]
+pub struct Config {
+    /// Base name of the trajectory file, without extension.
+    #[arg(default_value = "traj")]
+    pub filekey: String,
+
+    /// Render a synthetic trajectory end-to-end into a temp directory,
+    /// verify the output is a valid GIF and print PASS/FAIL with timing.
+    #[arg(long)]
+    pub selftest: bool,
+
+    /// Directory holding the input files (and S3 download cache).
+    #[arg(long, default_value = "data")]
+    pub input_dir: String,
+
+    /// Directory the rendered output is written to.
+    #[arg(long, default_value = "data")]
+    pub output_dir: String,
+
+    /// Filename template for single-file outputs, relative to
+    /// `--output-dir`. Placeholders: `{filekey}`, `{mode}`, `{frames}`
+    /// (the planned frame count) and `{date}` (UTC `YYYY-MM-DD`).
+    /// Defaults to the usual `{filekey}_traj.gif`-style names; the
+    /// extension must match the chosen mode.
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_name: Option<String>,
+
+    /// S3 bucket to download `{filekey}.csv` from when it is not found locally.
+    #[arg(long)]
+    pub bucket: Option<String>,
+
+    /// Abort an S3 download when the object is larger than this many bytes
+    /// (checked with a head request first; unlimited when omitted).
+    #[arg(long)]
+    pub s3_max_bytes: Option<u64>,
+
+    /// First sample index to render.
+    #[arg(long, default_value_t = 0)]
+    pub start: usize,
+
+    /// Last sample index to render (defaults to the trajectory length).
+    #[arg(long)]
+    pub end_frame: Option<usize>,
+
+    /// Number of samples to advance between rendered frames.
+    #[arg(long, default_value_t = 10)]
+    pub skip: usize,
+
+    /// Target animation length in seconds: derives `--skip` from the frame
+    /// delay (`--secs`) so the whole trajectory spans this duration. The
+    /// computed skip is reported.
+    #[arg(long)]
+    pub target_duration: Option<f64>,
+
+    /// Number of samples kept in the visible trail.
+    #[arg(long, default_value_t = 160)]
+    pub trail: usize,
+
+    /// Draw only every Nth point of the trail polyline (first and last are
+    /// always kept). Purely a per-frame drawing simplification; the data
+    /// itself is untouched, unlike `--decimate`.
+    #[arg(long, default_value_t = 1)]
+    pub trail_stride: usize,
+
+    /// Delay between GIF frames, in seconds.
+    #[arg(long, default_value_t = 0.05)]
+    pub secs: f64,
+
+    /// Set each GIF frame's delay from the actual `t` gap to the next
+    /// frame instead of the constant `--secs`, so playback tracks real
+    /// elapsed time even with irregular sampling.
+    #[arg(long)]
+    pub delay_from_time: bool,
+
+    /// Playback speed factor for `--delay-from-time`: frame delays are
+    /// the data-time gaps multiplied by this (2.0 plays at half speed).
+    #[arg(long, default_value_t = 1.0)]
+    pub time_scale: f64,
+
+    /// Output width in pixels.
+    #[arg(long, default_value_t = 800)]
+    pub width: u32,
+
+    /// Output height in pixels.
+    #[arg(long, default_value_t = 600)]
+    pub height: u32,
+
+    /// Output mode.
+    #[arg(long, value_enum, default_value_t = Mode::Gif)]
+    pub mode: Mode,
+
+    /// Render a single mid-trajectory frame to a PNG and exit.
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Render only the frame whose trail window contains this time and
+    /// write it as `{filekey}_goto.png`, with the yaw that frame would
+    /// have in the full animation -- for pinpointing a known moment
+    /// without scrubbing through the GIF.
+    #[arg(long, value_name = "T")]
+    pub goto_time: Option<f64>,
+
+    /// Watch the input CSV for appended rows and keep a live PNG of the
+    /// newest frame up to date (`{filekey}_live.png`). Runs until Ctrl-C.
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Poll interval for `--watch`, in milliseconds.
+    #[arg(long, default_value_t = 1000)]
+    pub watch_interval_ms: u64,
+
+    /// Load the data and print statistics without rendering.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Instead of rendering, dump frame N's trail window as CSV
+    /// (sample index, plot coordinates, t, speed) plus the camera
+    /// parameters used, for debugging a suspicious frame. Goes to
+    /// `--stats-out` when given, otherwise stdout.
+    #[arg(long, value_name = "N")]
+    pub dump_frame: Option<usize>,
+
+    /// Print a data quality report (nulls, timestamp monotonicity, delta-t
+    /// spread) without rendering. `--stats-out` also writes it as JSON.
+    #[arg(long)]
+    pub qc: bool,
+
+    /// Print the recording's temporal structure (start/end, duration,
+    /// gaps over the `--gap-threshold` — default three median steps —
+    /// and sample coverage against the median rate) before rendering.
+    /// Combine with `--dry-run` to check data health without an output.
+    #[arg(long)]
+    pub timeline: bool,
+
+    /// Fixed axis bounds as `xmin xmax ymin ymax zmin zmax` (data coordinates).
+    #[arg(long, num_args = 6, allow_negative_numbers = true)]
+    pub fixed_bounds: Option<Vec<f64>>,
+
+    /// Error out before rendering if any sample falls outside the
+    /// `--fixed-bounds` box, reporting the count and the actual data
+    /// extent. Catches stale bounds reused on a differently-scaled file.
+    #[arg(long, requires = "fixed_bounds")]
+    pub validate_bounds: bool,
+
+    /// Translate coordinates so this origin maps to (0,0,0) before bounds
+    /// and rendering: `center` (arena midpoint), `first` (first sample) or
+    /// an explicit `x,y,z`. The applied offset is logged for reversibility.
+    #[arg(long)]
+    pub origin: Option<String>,
+
+    /// Resample onto a uniform time grid at this frequency (Hz) via linear
+    /// interpolation over `t`, making time-based stepping exact. Drops any
+    /// `--keep-columns` extras, which cannot be interpolated.
+    #[arg(long, value_name = "HZ")]
+    pub resample: Option<f64>,
+
+    /// Plot the y and z columns in their natural order. By default the z
+    /// column is drawn on the vertical plot axis and y on the depth axis,
+    /// since tracking data conventionally stores height in z; this flag
+    /// disables that swap so `(x,y,z)` plots as-is.
+    #[arg(long)]
+    pub no_axis_swap: bool,
+
+    /// Normalize each coordinate to [0, 1] before rendering.
+    #[arg(long)]
+    pub normalize: bool,
+
+    /// Expand the smaller auto-bound ranges to match the largest so one
+    /// data unit is the same visual length on every axis. Mostly redundant
+    /// with `--normalize`, which already maps every axis to [0, 1] (and
+    /// thereby discards the real aspect ratio this flag preserves).
+    #[arg(long)]
+    pub equal_aspect: bool,
+
+    /// Moving-average window (in samples) applied to the coordinates.
+    #[arg(long)]
+    pub smooth: Option<usize>,
+
+    /// Keep only every Nth sample of the input.
+    #[arg(long)]
+    pub decimate: Option<usize>,
+
+    /// Color the trail by elapsed time.
+    #[arg(long)]
+    pub color_by_time: bool,
+
+    /// Color the trail by instantaneous speed.
+    #[arg(long)]
+    pub color_by_speed: bool,
+
+    /// Color the trail by acceleration magnitude (blue to red). Noisy
+    /// accelerations can be tamed with `--smooth`, which is applied to the
+    /// coordinates before kinematics are derived.
+    #[arg(long)]
+    pub color_by_accel: bool,
+
+    /// Fixed upper bound for the speed color scale (auto when omitted).
+    #[arg(long)]
+    pub speed_max: Option<f64>,
+
+    /// Fixed upper bound for the acceleration color scale (auto when
+    /// omitted).
+    #[arg(long)]
+    pub accel_max: Option<f64>,
+
+    /// Color scheme: one switch for background, body, text and
+    /// projection colors. Explicit color flags still override the
+    /// theme's defaults.
+    #[arg(long, value_enum, default_value_t = Theme::Light)]
+    pub theme: Theme,
+
+    /// Color for the caption, axis labels and annotation text, as
+    /// `#rrggbb` (overrides the theme default).
+    #[arg(long)]
+    pub text_color: Option<String>,
+
+    /// Draw annotation text with a halo in this `#rrggbb` color, keeping it
+    /// legible over light or colorful backgrounds.
+    #[arg(long)]
+    pub text_outline: Option<String>,
+
+    /// Color of the bulk of the trail, as `#rrggbb` (the theme ink when
+    /// omitted).
+    #[arg(long)]
+    pub trail_color: Option<String>,
+
+    /// Draw the last `--lead-length` trail segments in this `#rrggbb`
+    /// color, pulling the eye to where the point is heading without a
+    /// full gradient.
+    #[arg(long)]
+    pub lead_color: Option<String>,
+
+    /// Number of trailing segments drawn in `--lead-color`.
+    #[arg(long, default_value_t = 10, value_name = "N")]
+    pub lead_length: usize,
+
+    /// Where the chart caption goes, for layouts where the trajectory or
+    /// other decorations compete for the top strip.
+    #[arg(long, value_enum, default_value_t = CaptionPos::Top)]
+    pub caption_pos: CaptionPos,
+
+    /// Draw the convex hull of the ground-plane positions as an outline
+    /// on the floor, a home-range-style spatial-extent summary (its area
+    /// is also reported in the stats).
+    #[arg(long)]
+    pub show_hull: bool,
+
+    /// Break the trail (and its projections) where consecutive samples
+    /// are more than this many seconds apart, so tracking dropouts show
+    /// as gaps instead of straight teleports.
+    #[arg(long, value_name = "SECONDS")]
+    pub gap_threshold: Option<f64>,
+
+    /// Render only frames whose leading sample satisfies this predicate,
+    /// written as `column op value` (e.g. `speed > 5` or `z < 2`).
+    /// Columns: x, y, z, t (data coordinates), speed, accel; operators:
+    /// `<`, `<=`, `>`, `>=`, `==`, `!=`.
+    #[arg(long, value_name = "EXPR")]
+    pub filter_expr: Option<String>,
+
+    /// Insert a short labeled black separator before these GIF frames, as
+    /// `FRAME:NAME` pairs (e.g. `0:baseline,120:stimulus`), delineating
+    /// behavioral epochs concatenated into one animation.
+    #[arg(long, value_delimiter = ',', value_name = "FRAME:NAME")]
+    pub chapter_markers: Vec<String>,
+
+    /// Gradient for the color-by modes, the heatmap and the 3D density.
+    #[arg(long, value_enum, default_value_t = Colormap::Viridis)]
+    pub colormap: Colormap,
+
+    /// Color the trail by any numeric column of the input (heart rate, a
+    /// model score, ...) through the selected `--colormap`, generalizing
+    /// the built-in kinematic color-by modes.
+    #[arg(long, value_name = "NAME")]
+    pub color_by_column: Option<String>,
+
+    /// Fixed `lo hi` color range for `--color-by-column`; computed from
+    /// the data when omitted.
+    #[arg(long, num_args = 2, allow_negative_numbers = true, requires = "color_by_column")]
+    pub column_range: Option<Vec<f64>>,
+
+    /// Pin the color-by scale across a batch: each run merges its
+    /// speed/acceleration maxima into this JSON file and colors with the
+    /// merged range. Pre-fill the file to fix the scale outright, or run
+    /// the batch twice so the second pass uses the global maxima.
+    #[arg(long)]
+    pub shared_scale_file: Option<PathBuf>,
+
+    /// Fade the trail like a comet: opacity ramps from fully transparent
+    /// at the oldest sample to fully opaque at the leading point. Combines
+    /// with the color-by modes and `--label-col` coloring.
+    #[arg(long)]
+    pub comet: bool,
+
+    /// Apply the active color-by scheme to the wall projections too,
+    /// instead of their fixed per-plane colors.
+    #[arg(long)]
+    pub color_projections: bool,
+
+    /// Body drawing style.
+    #[arg(long, value_enum, default_value_t = RenderStyle::Line)]
+    pub render_style: RenderStyle,
+
+    /// Skip drawing the 3D body (trail, markers, leading point) and show
+    /// only the selected projections.
+    #[arg(long)]
+    pub hide_body: bool,
+
+    /// Center the 3D chart in a sub-area matching the data's
+    /// horizontal-to-vertical extent ratio, filling the margins with the
+    /// background color, so the trajectory is not stretched at arbitrary
+    /// output resolutions.
+    #[arg(long)]
+    pub letterbox: bool,
+
+    /// Draw projections on the 3D box walls or as separate 2D panels.
+    #[arg(long, value_enum, default_value_t = ProjectionLayout::OnBox)]
+    pub projection_layout: ProjectionLayout,
+
+    /// Comma-separated projection planes to draw (`xy`, `xz`, `yz`).
+    #[arg(long, default_value = "xy,xz,yz", value_delimiter = ',')]
+    pub projections: Vec<String>,
+
+    /// Named projection subset; overrides `--projections` when given.
+    /// "Floor" always means the ground plane, whichever data plane that
+    /// is under the current `--no-axis-swap` setting.
+    #[arg(long, value_enum)]
+    pub projection_preset: Option<ProjectionPreset>,
+
+    /// Additionally export each selected projection plane as its own 2D
+    /// animated GIF (`{filekey}_xy.gif`, `_xz.gif`, `_yz.gif`).
+    #[arg(long)]
+    pub export_projections: bool,
+
+    /// Opacity (0-1) of the wall projection lines, in every coloring mode.
+    #[arg(long, default_value_t = 0.3)]
+    pub projection_alpha: f64,
+
+    /// Opacity override for the `xy` projection alone.
+    #[arg(long)]
+    pub xy_alpha: Option<f64>,
+
+    /// Opacity override for the `xz` projection alone.
+    #[arg(long)]
+    pub xz_alpha: Option<f64>,
+
+    /// Opacity override for the `yz` projection alone.
+    #[arg(long)]
+    pub yz_alpha: Option<f64>,
+
+    /// At the leading point, draw faint tie lines down and across to its
+    /// projection points on the selected walls, making the shadow
+    /// correspondence explicit.
+    #[arg(long)]
+    pub tie_lines: bool,
+
+    /// Opacity (0-1) of the `--tie-lines` connectors.
+    #[arg(long, default_value_t = 0.4)]
+    pub tie_line_alpha: f64,
+
+    /// Draw faint reference gridlines on the floor plane every SPACING
+    /// data units, as a ground-plane distance cue under the rotating
+    /// camera (distinct from the axis grid).
+    #[arg(long, value_name = "SPACING")]
+    pub floor_grid: Option<f64>,
+
+    /// Tick count on the drawn horizontal axis (plotters picks the
+    /// default). The counts refer to the chart axes, i.e. after the
+    /// default data-y/data-z swap.
+    #[arg(long, value_name = "N")]
+    pub x_ticks: Option<usize>,
+
+    /// Tick count on the drawn vertical axis.
+    #[arg(long, value_name = "N")]
+    pub y_ticks: Option<usize>,
+
+    /// Tick count on the drawn depth axis.
+    #[arg(long, value_name = "N")]
+    pub z_ticks: Option<usize>,
+
+    /// Decimal places in the axis tick labels (default lets plotters
+    /// format each value).
+    #[arg(long, value_name = "DIGITS")]
+    pub tick_precision: Option<usize>,
+
+    /// Number of bins per axis for the heatmap and density3d modes.
+    #[arg(long, default_value_t = 50)]
+    pub bins: usize,
+
+    /// Draw a labeled scale bar of this data-unit length on 2D outputs
+    /// (currently the heatmap), for publication figures.
+    #[arg(long)]
+    pub scale_bar: Option<f64>,
+
+    /// Corner the scale bar is drawn in.
+    #[arg(long, value_enum, default_value_t = Corner::BottomRight)]
+    pub scale_bar_pos: Corner,
+
+    /// Write the computed trajectory statistics as JSON to this path.
+    #[arg(long)]
+    pub stats_out: Option<PathBuf>,
+
+    /// Write the 2D occupancy histogram as CSV (`x_center,y_center,count`)
+    /// to this path, with `--bins` bins per axis over the XY bounds. Bins
+    /// are half-open `[edge, edge + width)` starting at the axis minimum.
+    #[arg(long)]
+    pub occupancy_out: Option<PathBuf>,
+
+    /// Write the resolved run parameters (full configuration plus derived
+    /// values like auto-computed bounds and effective frame count) as JSON
+    /// to this path after rendering.
+    #[arg(long)]
+    pub emit_params: Option<PathBuf>,
+
+    /// Write the per-frame camera (pitch, yaw, scale and the resulting
+    /// 4x4 projection matrix) as CSV to this path, so external tools can
+    /// composite other 3D content with the exact camera each frame used.
+    #[arg(long, value_name = "PATH")]
+    pub emit_camera: Option<PathBuf>,
+
+    /// Play the animation forward then backward in one loop.
+    #[arg(long)]
+    pub bounce: bool,
+
+    /// Render frames from the end of the trajectory back to the start
+    /// (fully reversed playback, unlike `--bounce`), for reviewing
+    /// approach-to-event sequences. Trails and time annotations still
+    /// follow the actual sample times.
+    #[arg(long)]
+    pub reverse: bool,
+
+    /// Scale the trail length inversely with speed (argument is the
+    /// reference speed at which the configured `--trail` applies).
+    #[arg(long)]
+    pub adaptive_trail: Option<f64>,
+
+    /// Cap the trail window by time instead of sample count: keep the
+    /// samples within S time units behind the leading sample. Gives a
+    /// temporally consistent trail under irregular sampling; overrides
+    /// `--trail` and `--adaptive-trail`.
+    #[arg(long, value_name = "S")]
+    pub trail_seconds: Option<f64>,
+
+    /// Annotate frames with the dominant oscillation period of `z`.
+    #[arg(long)]
+    pub show_period: bool,
+
+    /// Annotate frames with the sample range and time span the trail
+    /// window covers, e.g. `samples 400-560, t 10.20-14.30`.
+    #[arg(long)]
+    pub show_window: bool,
+
+    /// Annotate frames with the cumulative turning angle and the
+    /// straightness index (net displacement / path length), a quick
+    /// movement-complexity readout.
+    #[arg(long)]
+    pub show_tortuosity: bool,
+
+    /// Tile the main trajectory and every `--overlay` trajectory into an
+    /// RxC grid (e.g. `2x3`) instead of overlaying them.
+    #[arg(long)]
+    pub grid: Option<String>,
+
+    /// Instead of animating, tile RxC evenly-spaced frames into a single
+    /// captioned PNG (`{filekey}_contact.png`) for review at a glance.
+    #[arg(long, value_name = "RxC")]
+    pub contact_sheet: Option<String>,
+
+    /// Additional filekeys rendered as overlaid trajectories.
+    #[arg(long, value_delimiter = ',')]
+    pub overlay: Vec<String>,
+
+    /// Column-prefix groups of body parts tracked in one file (e.g.
+    /// `head,tail` for `head_x/head_y/head_z` and `tail_*` columns), each
+    /// rendered as its own colored trail.
+    #[arg(long, value_delimiter = ',')]
+    pub segments: Vec<String>,
+
+    /// Connect the leading points of all `--segments` trails in every
+    /// frame, sketching the body skeleton.
+    #[arg(long, requires = "segments")]
+    pub skeleton: bool,
+
+    /// Shift each trajectory's `t` origin so overlaid trajectories show the
+    /// same relative moment at the same frame. Timestamps are kept as
+    /// loaded when omitted.
+    #[arg(long, value_enum, default_value_t = AlignTime::None)]
+    pub align_time: AlignTime,
+
+    /// Radius in pixels of the per-sample markers along the trail.
+    #[arg(long, default_value_t = 1)]
+    pub point_size: u32,
+
+    /// Draw a marker every N samples of the trail (0 disables markers).
+    #[arg(long, default_value_t = 1)]
+    pub point_every: usize,
+
+    /// Render the static chart background (fill, axes) once and reuse it
+    /// for every frame. Only effective with a static camera, i.e. constant
+    /// `--camera-keyframes`; ignored (with a warning) otherwise.
+    #[arg(long)]
+    pub cache_background: bool,
+
+    /// Quantize the GIF palette to this many colors (2-256) for smaller
+    /// files. Switches to the internal GIF encoder.
+    #[arg(long)]
+    pub gif_colors: Option<usize>,
+
+    /// Downscale factor (0-1] applied to rendered frames before GIF
+    /// encoding; frames are rendered at full resolution then box-filtered.
+    #[arg(long)]
+    pub gif_scale: Option<f64>,
+
+    /// Repeat the final GIF frame this many times so the endpoint registers
+    /// before the loop restarts.
+    #[arg(long, default_value_t = 0)]
+    pub hold_last: usize,
+
+    /// Delay in milliseconds for the held final frames (internal encoder
+    /// only, i.e. with --gif-colors/--gif-scale; otherwise --secs applies).
+    #[arg(long)]
+    pub hold_delay_ms: Option<u64>,
+
+    /// In png-sequence mode, name files by the frame's time instead of its
+    /// index (`{filekey}_t00010.250.png`). Names are zero-padded so they
+    /// sort in time order; frames sharing a time overwrite each other.
+    #[arg(long)]
+    pub name_by_time: bool,
+
+    /// Trust that `t` is already non-decreasing and skip the monotonicity
+    /// scan (and any re-sort) while loading, for a speed boost on large
+    /// files. With unsorted input this yields garbage; verify once with
+    /// `--qc`, then assert it thereafter.
+    #[arg(long)]
+    pub assume_sorted: bool,
+
+    /// Drop the first N samples right after loading, before bounds and
+    /// statistics, to discard initial tracking noise. Unlike `--start`
+    /// the dropped samples do not influence auto-bounds or stats.
+    #[arg(long, default_value_t = 0)]
+    pub warmup_frames: usize,
+
+    /// Negate the `x` column (about zero) while loading, to correct
+    /// mirrored tracking data.
+    #[arg(long)]
+    pub flip_x: bool,
+
+    /// Negate the `y` column (about zero) while loading.
+    #[arg(long)]
+    pub flip_y: bool,
+
+    /// Negate the `z` column (about zero) while loading.
+    #[arg(long)]
+    pub flip_z: bool,
+
+    /// Estimate the arena floor from a low percentile of `z` and use it as
+    /// the projection plane and vertical axis minimum instead of -1.0.
+    #[arg(long)]
+    pub detect_floor: bool,
+
+    /// Render a synthetic demo trajectory instead of loading any file.
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Seed for the synthetic demo trajectory.
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+
+    /// Read the trajectory from this SQLite database instead of a file,
+    /// running `--query` (or `SELECT x, y, z, t FROM {filekey}`) via
+    /// rusqlite. The filekey still names the output.
+    #[arg(long, value_name = "PATH")]
+    pub sqlite: Option<std::path::PathBuf>,
+
+    /// SQL query for `--sqlite`. The selected columns feed the same
+    /// normalization as a CSV, so they should include `x`, `y`, `z`, `t`.
+    #[arg(long, value_name = "SQL", requires = "sqlite")]
+    pub query: Option<String>,
+
+    /// Read the CSV from stdin instead of a file, for pipeline use
+    /// (`cat data.csv | traj_viewer mykey --stdin`). The filekey still
+    /// names the output.
+    #[arg(long)]
+    pub stdin: bool,
+
+    /// Read large local CSVs through polars' memory-mapped, low-memory
+    /// path without rechunking, trading some parse speed for a much lower
+    /// peak footprint. The file must not change during the run. Not
+    /// applicable to `--stdin`, `--decimal-comma` or S3 inputs, which all
+    /// go through an in-memory buffer.
+    #[arg(long)]
+    pub mmap: bool,
+
+    /// Load `{filekey}_part1`, `{filekey}_part2`, ... and stack them into
+    /// one recording, re-basing `t` so time runs continuously across the
+    /// parts. Used for long sessions split across files by the recorder.
+    #[arg(long)]
+    pub concat_parts: bool,
+
+    /// Parse CSVs that use `,` as the decimal separator and `;` as the
+    /// field delimiter (common in locale exports).
+    #[arg(long)]
+    pub decimal_comma: bool,
+
+    /// Embed provenance tEXt chunks (filekey, frame index, `t0`, source
+    /// path, tool version) in the PNGs written by the png-sequence and
+    /// preview modes, so archival workflows can trace each frame back to
+    /// its data.
+    #[arg(long)]
+    pub embed_metadata: bool,
+
+    /// Treat the `t` column as a datetime even when it loads as a string.
+    #[arg(long)]
+    pub t_is_datetime: bool,
+
+    /// Strip this unit suffix (e.g. `cm`) from string coordinate values
+    /// before numeric parsing.
+    #[arg(long)]
+    pub unit_suffix: Option<String>,
+
+    /// Per-sample confidence column used by `--min-confidence`.
+    #[arg(long)]
+    pub confidence_col: Option<String>,
+
+    /// Behavior label column: each distinct label gets its own trail color
+    /// (null samples stay neutral grey) and a legend maps colors to labels.
+    #[arg(long)]
+    pub label_col: Option<String>,
+
+    /// Drop samples whose confidence is below this value (needs
+    /// `--confidence-col`). The trail bridges the removed samples.
+    #[arg(long, requires = "confidence_col", allow_negative_numbers = true)]
+    pub min_confidence: Option<f64>,
+
+    /// Extra input columns to keep alongside `x,y,z,t` (comma-separated),
+    /// so they show up in `--qc` reports. A warning is printed for
+    /// requested columns the file does not have.
+    #[arg(long, value_delimiter = ',')]
+    pub keep_columns: Vec<String>,
+
+    /// Scale of the 3D projection; larger values frame the box tighter.
+    #[arg(long, default_value_t = 0.8)]
+    pub view_scale: f64,
+
+    /// Yaw the oscillating camera starts from at frame 0, so side-by-side
+    /// renders step through identical angles.
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub start_yaw: f64,
+
+    /// Initial sweep direction of the yaw oscillation: `1` or `-1`.
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub start_delta_sign: f64,
+
+    /// Mark the trajectory position at this time with a persistent marker
+    /// and highlight frames whose trail window contains it (repeatable).
+    #[arg(long = "mark-time")]
+    pub mark_times: Vec<f64>,
+
+    /// Draw an arrow from the leading point along the current velocity.
+    #[arg(long)]
+    pub show_velocity_arrow: bool,
+
+    /// Length of the velocity arrow in data units per unit of speed.
+    #[arg(long, default_value_t = 0.1)]
+    pub arrow_scale: f64,
+
+    /// Process the trajectory in chunks of this many samples, writing one
+    /// output per chunk (`{filekey}_part{n}`). Chunks overlap the previous
+    /// one by `--trail` samples so trails stay continuous.
+    #[arg(long)]
+    pub chunk_size: Option<usize>,
+
+    /// Abort before rendering if the estimated working-set memory (sample
+    /// arrays plus frame buffers) would exceed this many megabytes,
+    /// instead of getting OOM-killed opaquely on huge files.
+    #[arg(long, value_name = "MB")]
+    pub max_memory_mb: Option<usize>,
+
+    /// Scripted camera path: `frame,pitch,yaw,scale` keyframes separated by
+    /// `;` (or a path to a file with one keyframe per line). The projection
+    /// parameters are linearly interpolated between keyframes, overriding
+    /// the automatic yaw oscillation.
+    #[arg(long)]
+    pub camera_keyframes: Option<String>,
+
+    /// Number of tokio worker threads (1 uses the current-thread runtime;
+    /// defaults to one per CPU core).
+    #[arg(long)]
+    pub threads: Option<usize>,
+
+    /// Format of log output.
+    #[arg(long, value_enum, default_value_t = LogFormat::Plain)]
+    pub log_format: LogFormat,
+
+    /// Append a one-line summary of each run (timestamp, filekey, frames,
+    /// elapsed, output path, status) to this file, as an audit trail for
+    /// batch processing. Concurrent invocations are serialized with a
+    /// file lock.
+    #[arg(long)]
+    pub log_file: Option<PathBuf>,
+
+    /// Print progress details.
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl Config {
+    /// Whether any per-segment color-by mode is active.
+    pub fn color_by_active(&self) -> bool {
+        self.color_by_time
+            || self.color_by_speed
+            || self.color_by_accel
+            || self.color_by_column.is_some()
+    }
+
+    /// The projection planes to draw: the `--projection-preset` resolved
+    /// against `--no-axis-swap` when given, otherwise the literal
+    /// `--projections` list.
+    pub fn projection_planes(&self) -> Vec<String> {
+        let (floor, back) = if self.no_axis_swap {
+            ("xz", "xy")
+        } else {
+            ("xy", "xz")
+        };
+        match self.projection_preset {
+            Some(ProjectionPreset::FloorOnly) => vec![floor.into()],
+            Some(ProjectionPreset::FloorAndBack) => vec![floor.into(), back.into()],
+            Some(ProjectionPreset::All) => vec!["xy".into(), "xz".into(), "yz".into()],
+            None => self.projections.clone(),
+        }
+    }
+}
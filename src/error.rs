@@ -0,0 +1,46 @@
+//! Error type shared across the loading, analysis and rendering stages.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// All errors surfaced by the trajectory viewer.
+#[derive(Error, Debug)]
+pub enum TrajViewerError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("polars error: {0}")]
+    Polars(#[from] polars::error::PolarsError),
+
+    #[error("input file not found: {0}")]
+    NotFound(PathBuf),
+
+    #[error("{0} contains no data rows")]
+    Empty(PathBuf),
+
+    #[error("S3 download failed for key {key}: {message}")]
+    S3 { key: String, message: String },
+
+    #[error("truncated download for key {key}: got {got} of {expected} bytes")]
+    TruncatedDownload {
+        key: String,
+        got: u64,
+        expected: u64,
+    },
+
+    #[error("bad trajectory data: {0}")]
+    BadData(String),
+
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[error("drawing error: {0}")]
+    Drawing(String),
+
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
@@ -0,0 +1,86 @@
+//! Structured JSON-lines logging for batch/observability use.
+//!
+//! With `--log-format json`, significant events are written to stderr as one
+//! JSON object per line, each carrying a unix timestamp.
+
+use serde::Serialize;
+
+use crate::config::{Config, LogFormat};
+
+/// A significant event in a run's lifecycle.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    LoadStart { filekey: &'a str },
+    LoadEnd { filekey: &'a str, rows: usize },
+    /// Offset subtracted from the coordinates by `--origin`, recorded so
+    /// the translation can be undone.
+    OriginShift { filekey: &'a str, dx: f64, dy: f64, dz: f64 },
+    FramesRendered { frames: usize },
+    OutputWritten { path: &'a str },
+    Error { message: &'a str },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    ts: f64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Append a one-line summary of the run to `--log-file`; a no-op when the
+/// flag is unset. The file is exclusively locked for the write so
+/// concurrent batch invocations don't interleave garbled lines. Failures
+/// are reported as warnings rather than aborting a finished run.
+pub fn log_run_summary(
+    config: &Config,
+    frames: usize,
+    elapsed: std::time::Duration,
+    output: &str,
+    status: &str,
+) {
+    let Some(path) = &config.log_file else {
+        return;
+    };
+    let result = (|| -> std::io::Result<()> {
+        use fs2::FileExt;
+        use std::io::Write;
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.lock_exclusive()?;
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        let line = format!(
+            "{ts:.3}\t{}\t{frames} frames\t{:.3}s\t{output}\t{status}\n",
+            config.filekey,
+            elapsed.as_secs_f64()
+        );
+        let written = file.write_all(line.as_bytes());
+        let _ = file.unlock();
+        written
+    })();
+    if let Err(e) = result {
+        eprintln!("warning: cannot append to --log-file: {e}");
+    }
+}
+
+/// Emit `event` to stderr when JSON logging is enabled; a no-op otherwise.
+pub fn emit(config: &Config, event: Event) {
+    if config.log_format != LogFormat::Json {
+        return;
+    }
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0);
+    let record = Record { ts, event: &event };
+    match serde_json::to_string(&record) {
+        Ok(line) => eprintln!("{line}"),
+        Err(e) => eprintln!("{{\"event\":\"error\",\"message\":\"log serialization: {e}\"}}"),
+    }
+}
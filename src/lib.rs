@@ -0,0 +1,15 @@
+//! 3D trajectory viewer.
+//!
+//! Loads tracked 3D trajectories (`x`, `y`, `z`, `t` columns) from CSV,
+//! Parquet, Arrow IPC or S3 and renders them as rotating 3D animations,
+//! PNG sequences or occupancy heatmaps.
+
+pub mod analysis;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod loader;
+pub mod render;
+
+pub use config::Config;
+pub use error::TrajViewerError;
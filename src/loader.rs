@@ -0,0 +1,1177 @@
+//! Loading of trajectory data from local files or S3.
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use polars::prelude::*;
+
+use crate::config::{AlignTime, Config};
+use crate::error::TrajViewerError;
+
+/// Columns every trajectory DataFrame is normalized to, in order.
+pub const TRAJ_COLUMNS: [&str; 4] = ["x", "y", "z", "t"];
+
+/// Load the trajectory for `filekey`, normalized to the `x`, `y`, `z`, `t`
+/// columns with nulls forward-filled.
+///
+/// Looks for `{input_dir}/{filekey}.csv` (or `.parquet`, `.feather`,
+/// `.arrow`) first; when none exists and `--bucket` is set, downloads
+/// `{filekey}.csv` from S3 and caches it in `input_dir` for the next run.
+pub async fn load_csv(config: &Config) -> Result<DataFrame, TrajViewerError> {
+    load_filekey(&config.filekey, config).await
+}
+
+/// Same as [`load_csv`] but for an arbitrary filekey (used by overlays).
+pub async fn load_filekey(filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let df = if config.concat_parts {
+        load_parts(filekey, config).await?
+    } else {
+        load_raw(filekey, config).await?
+    };
+    let mut df = normalize(df, config)?;
+    align_time(&mut df, filekey, config)?;
+    apply_origin(&mut df, filekey, config)?;
+    if let Some(hz) = config.resample {
+        if hz <= 0.0 {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "--resample frequency must be positive, got {hz}"
+            )));
+        }
+        df = resample_uniform(&df, hz)?;
+        if config.verbose {
+            println!("resampled {filekey} to {hz} Hz: {} samples", df.height());
+        }
+    }
+    Ok(df)
+}
+
+/// Re-read the local CSV for a `--watch` poll, tolerating a partially
+/// written last line by parsing only up to the final newline.
+pub fn load_watch_snapshot(filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let path = Path::new(&config.input_dir).join(format!("{filekey}.csv"));
+    if !path.exists() {
+        return Err(TrajViewerError::NotFound(path));
+    }
+    let mut bytes = std::fs::read(&path)?;
+    if let Some(pos) = bytes.iter().rposition(|b| *b == b'\n') {
+        bytes.truncate(pos + 1);
+    }
+    let df = read_csv_bytes(bytes, config)?;
+    if df.height() == 0 {
+        return Err(TrajViewerError::Empty(path));
+    }
+    let df = df.select(selected_columns(&df, config))?;
+    normalize(df, config)
+}
+
+/// Translate the coordinates so the `--origin` point maps to (0,0,0),
+/// before bounds and statistics see them. The applied offset is emitted as
+/// an `origin_shift` event (and printed under `--verbose`) so the
+/// translation can be undone.
+pub fn apply_origin(
+    df: &mut DataFrame,
+    filekey: &str,
+    config: &Config,
+) -> Result<(), TrajViewerError> {
+    let Some(spec) = &config.origin else {
+        return Ok(());
+    };
+
+    let column = |name: &str| -> Result<Float64Chunked, TrajViewerError> {
+        Ok(df.column(name)?.cast(&DataType::Float64)?.f64()?.clone())
+    };
+
+    let offset = match spec.as_str() {
+        "center" => {
+            // Arena midpoint from metadata when given, data midpoint else.
+            let meta = load_meta(filekey, config).unwrap_or_default();
+            let mid = |lo: Option<f64>, hi: Option<f64>, values: &Float64Chunked| {
+                match (lo, hi) {
+                    (Some(lo), Some(hi)) => (lo + hi) / 2.0,
+                    _ => {
+                        let lo = values.min().unwrap_or(0.0);
+                        let hi = values.max().unwrap_or(0.0);
+                        (lo + hi) / 2.0
+                    }
+                }
+            };
+            (
+                mid(meta.xmin, meta.xmax, &column("x")?),
+                mid(meta.ymin, meta.ymax, &column("y")?),
+                mid(meta.zmin, meta.zmax, &column("z")?),
+            )
+        }
+        "first" => (
+            column("x")?.get(0).unwrap_or(0.0),
+            column("y")?.get(0).unwrap_or(0.0),
+            column("z")?.get(0).unwrap_or(0.0),
+        ),
+        spec => {
+            let parts: Vec<f64> = spec
+                .split(',')
+                .filter_map(|p| p.trim().parse().ok())
+                .collect();
+            if parts.len() != 3 {
+                return Err(TrajViewerError::InvalidConfig(format!(
+                    "--origin expects `center`, `first` or `x,y,z`, got `{spec}`"
+                )));
+            }
+            (parts[0], parts[1], parts[2])
+        }
+    };
+
+    for (name, shift) in [("x", offset.0), ("y", offset.1), ("z", offset.2)] {
+        if shift == 0.0 {
+            continue;
+        }
+        let col = df.column(name)?.cast(&DataType::Float64)?;
+        let shifted = col.f64()?.apply(|v| v.map(|v| v - shift)).into_series();
+        df.replace(name, shifted)?;
+    }
+
+    crate::events::emit(config, crate::events::Event::OriginShift {
+        filekey,
+        dx: offset.0,
+        dy: offset.1,
+        dz: offset.2,
+    });
+    if config.verbose {
+        println!(
+            "origin shift for {filekey}: ({}, {}, {})",
+            offset.0, offset.1, offset.2
+        );
+    }
+    Ok(())
+}
+
+/// Shift the `t` column by the per-trajectory `--align-time` origin, so
+/// frame N shows the same relative moment for every overlaid trajectory.
+pub fn align_time(df: &mut DataFrame, filekey: &str, config: &Config) -> Result<(), TrajViewerError> {
+    let offset = match config.align_time {
+        AlignTime::None => return Ok(()),
+        AlignTime::Start => df
+            .column("t")?
+            .cast(&DataType::Float64)?
+            .f64()?
+            .get(0)
+            .unwrap_or(0.0),
+        AlignTime::Event => {
+            match load_meta(filekey, config).and_then(|meta| meta.event_time) {
+                Some(t) => t,
+                None => {
+                    return Err(TrajViewerError::InvalidConfig(format!(
+                        "--align-time event needs `event_time` in {filekey}.meta.json"
+                    )))
+                }
+            }
+        }
+    };
+    if offset == 0.0 {
+        return Ok(());
+    }
+
+    let t = df.column("t")?.cast(&DataType::Float64)?;
+    let shifted = t.f64()?.apply(|v| v.map(|v| v - offset)).into_series();
+    df.replace("t", shifted)?;
+    Ok(())
+}
+
+/// Convert a normalized DataFrame into an `n x 4` array of `x`, `y`, `z`,
+/// `t` values. Verifies up front that those columns lead the frame in
+/// order, are numeric (integers are cast) and hold no null or NaN, so
+/// callers get a message naming the offending column instead of an opaque
+/// `to_ndarray` failure.
+pub fn df_to_xyzt(df: &DataFrame) -> Result<ndarray::Array2<f64>, TrajViewerError> {
+    let names = df.get_column_names();
+    for (i, expected) in TRAJ_COLUMNS.iter().enumerate() {
+        match names.get(i) {
+            Some(name) if name == expected => {}
+            Some(name) => {
+                return Err(TrajViewerError::BadData(format!(
+                    "expected column {i} to be `{expected}`, found `{name}`"
+                )))
+            }
+            None => {
+                return Err(TrajViewerError::BadData(format!(
+                    "missing column `{expected}`"
+                )))
+            }
+        }
+    }
+    for name in TRAJ_COLUMNS {
+        let series = df.column(name)?;
+        if !series.dtype().is_numeric() {
+            return Err(TrajViewerError::BadData(format!(
+                "column `{name}` is {}, expected a numeric type",
+                series.dtype()
+            )));
+        }
+        if series.null_count() > 0 {
+            return Err(TrajViewerError::BadData(format!(
+                "column `{name}` has {} null value(s)",
+                series.null_count()
+            )));
+        }
+        let floats = series.cast(&DataType::Float64)?;
+        if floats.f64()?.into_no_null_iter().any(f64::is_nan) {
+            return Err(TrajViewerError::BadData(format!(
+                "column `{name}` contains NaN"
+            )));
+        }
+    }
+    Ok(df
+        .select(TRAJ_COLUMNS)?
+        .to_ndarray::<Float64Type>(IndexOrder::C)?)
+}
+
+/// Resample the trajectory onto a uniform time grid at `hz` samples per
+/// second, linearly interpolating `x`, `y` and `z` over `t`. Assumes `t`
+/// is non-decreasing (guaranteed after normalization) and keeps only the
+/// trajectory columns, since extras cannot be meaningfully interpolated.
+pub fn resample_uniform(df: &DataFrame, hz: f64) -> PolarsResult<DataFrame> {
+    let data = df
+        .select(TRAJ_COLUMNS)?
+        .to_ndarray::<Float64Type>(IndexOrder::C)?;
+    if data.nrows() < 2 {
+        return df.select(TRAJ_COLUMNS);
+    }
+
+    let t0 = data[[0, 3]];
+    let t1 = data[[data.nrows() - 1, 3]];
+    let step = 1.0 / hz;
+    let samples = ((t1 - t0) / step).floor() as usize + 1;
+
+    let mut xs = Vec::with_capacity(samples);
+    let mut ys = Vec::with_capacity(samples);
+    let mut zs = Vec::with_capacity(samples);
+    let mut ts = Vec::with_capacity(samples);
+    let mut j = 0;
+    for k in 0..samples {
+        let t = t0 + k as f64 * step;
+        while j + 2 < data.nrows() && data[[j + 1, 3]] < t {
+            j += 1;
+        }
+        let (ta, tb) = (data[[j, 3]], data[[j + 1, 3]]);
+        let f = ((t - ta) / (tb - ta).max(f64::EPSILON)).clamp(0.0, 1.0);
+        xs.push(data[[j, 0]] + f * (data[[j + 1, 0]] - data[[j, 0]]));
+        ys.push(data[[j, 1]] + f * (data[[j + 1, 1]] - data[[j, 1]]));
+        zs.push(data[[j, 2]] + f * (data[[j + 1, 2]] - data[[j, 2]]));
+        ts.push(t);
+    }
+
+    df!("x" => xs, "y" => ys, "z" => zs, "t" => ts)
+}
+
+/// The local paths `load_raw` would read `filekey` from, in probe order.
+fn input_candidates(filekey: &str, config: &Config) -> Vec<PathBuf> {
+    ["csv", "parquet", "feather", "arrow"]
+        .iter()
+        .map(|ext| Path::new(&config.input_dir).join(format!("{filekey}.{ext}")))
+        .collect()
+}
+
+/// Refuse configurations whose output paths would overwrite an input file
+/// — typically `--output-dir` pointing at the input folder with a
+/// colliding filename — before anything is written.
+pub fn check_output_collision(config: &Config) -> Result<(), TrajViewerError> {
+    let mut inputs = input_candidates(&config.filekey, config);
+    for filekey in &config.overlay {
+        inputs.extend(input_candidates(filekey, config));
+    }
+    // Canonicalize so `dir/../dir/file.csv` spellings still collide; only
+    // existing files canonicalize, which is exactly the data-loss case.
+    let inputs: Vec<PathBuf> = inputs
+        .into_iter()
+        .filter_map(|p| p.canonicalize().ok())
+        .collect();
+
+    let out_dir = Path::new(&config.output_dir);
+    let mut outputs: Vec<PathBuf> = ["_traj.gif", "_heatmap.png", "_density3d.gif", "_live.png"]
+        .iter()
+        .map(|suffix| out_dir.join(format!("{}{suffix}", config.filekey)))
+        .collect();
+    outputs.extend(config.stats_out.iter().cloned());
+    outputs.extend(config.occupancy_out.iter().cloned());
+    outputs.extend(config.emit_params.iter().cloned());
+    outputs.extend(config.emit_camera.iter().cloned());
+    outputs.extend(config.log_file.iter().cloned());
+
+    for out in outputs {
+        let Ok(out) = out.canonicalize() else { continue };
+        if inputs.contains(&out) {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "output path {} would overwrite the input file; \
+                 pick a different --output-dir or filename",
+                out.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Load the trajectory columns without forward-filling or flips, keeping
+/// nulls intact for quality-control inspection.
+pub async fn load_raw(filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let df = read_input(filekey, config).await?;
+    let df = df.select(selected_columns(&df, config))?;
+    check_parsed_columns(&df, config)?;
+    Ok(df)
+}
+
+/// `--concat-parts`: read `{filekey}_part1`, `{filekey}_part2`, ... in
+/// order and stack them into one recording, stopping at the first missing
+/// part number. A missing `_part1` is an error, so typos in the filekey
+/// still fail loudly instead of rendering nothing.
+async fn load_parts(filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    if config.stdin {
+        return Err(TrajViewerError::InvalidConfig(
+            "--concat-parts cannot be combined with --stdin".into(),
+        ));
+    }
+    let mut combined: Option<DataFrame> = None;
+    let mut parts = 0usize;
+    loop {
+        let part_key = format!("{filekey}_part{}", parts + 1);
+        let df = match load_raw(&part_key, config).await {
+            Ok(df) => df,
+            Err(TrajViewerError::NotFound(_)) if parts > 0 => break,
+            Err(e) => return Err(e),
+        };
+        combined = Some(match combined.take() {
+            Some(mut acc) => {
+                let df = rebase_part_time(&acc, df)?;
+                acc.vstack_mut(&df)?;
+                acc
+            }
+            None => df,
+        });
+        parts += 1;
+    }
+    let df = combined.expect("part 1 either loaded or errored");
+    println!("joined {parts} part(s): {} samples", df.height());
+    Ok(df)
+}
+
+/// Shift a part's `t` so it continues one sample step after the previous
+/// parts, for recorders that restart the clock in each file. Parts whose
+/// timestamps already run past the previous end are left untouched, as are
+/// non-numeric `t` columns (datetime strings are parsed later, in
+/// [`normalize`], where re-basing no longer applies).
+fn rebase_part_time(acc: &DataFrame, mut df: DataFrame) -> Result<DataFrame, TrajViewerError> {
+    let (Ok(prev), Ok(part)) = (
+        acc.column("t")?.cast(&DataType::Float64),
+        df.column("t")?.cast(&DataType::Float64),
+    ) else {
+        return Ok(df);
+    };
+    let prev = prev.f64()?;
+    let part = part.f64()?;
+    let (Some(prev_last), Some(part_first)) =
+        (prev.get(prev.len().saturating_sub(1)), part.get(0))
+    else {
+        return Ok(df);
+    };
+    if part_first > prev_last {
+        return Ok(df);
+    }
+
+    // Continue one step past the previous part, using its last interval as
+    // the step so the boundary gap matches the sampling rate.
+    let step = match prev.len() {
+        0 | 1 => 0.0,
+        n => (prev_last - prev.get(n - 2).unwrap_or(prev_last)).max(0.0),
+    };
+    let offset = prev_last + step - part_first;
+    let shifted = part.apply(|v| v.map(|v| v + offset)).into_series();
+    df.replace("t", shifted)?;
+    Ok(df)
+}
+
+/// Read the full input file for `filekey` without any column selection.
+async fn read_input(filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let csv_path = Path::new(&config.input_dir).join(format!("{filekey}.csv"));
+    let parquet_path = Path::new(&config.input_dir).join(format!("{filekey}.parquet"));
+    let feather_path = Path::new(&config.input_dir).join(format!("{filekey}.feather"));
+    let arrow_path = Path::new(&config.input_dir).join(format!("{filekey}.arrow"));
+
+    let (df, source) = if config.stdin {
+        let mut bytes = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)?;
+        (read_csv_bytes(bytes, config)?, PathBuf::from("<stdin>"))
+    } else if let Some(db) = &config.sqlite {
+        (read_sqlite(db, filekey, config)?, db.clone())
+    } else if csv_path.exists() {
+        (read_csv_path(&csv_path, config)?, csv_path)
+    } else if parquet_path.exists() {
+        (read_parquet_path(&parquet_path)?, parquet_path)
+    } else if feather_path.exists() {
+        (read_ipc_path(&feather_path)?, feather_path)
+    } else if arrow_path.exists() {
+        (read_ipc_path(&arrow_path)?, arrow_path)
+    } else if let Some(bucket) = &config.bucket {
+        let bytes = download_s3(bucket, &format!("{filekey}.csv"), config.s3_max_bytes).await?;
+        cache_download(&csv_path, &bytes, config.verbose);
+        (read_csv_bytes(bytes, config)?, csv_path)
+    } else {
+        return Err(TrajViewerError::NotFound(csv_path));
+    };
+
+    // A header-only file would otherwise fail deep in the ndarray
+    // conversion or render a zero-length animation.
+    if df.height() == 0 {
+        return Err(TrajViewerError::Empty(source));
+    }
+    Ok(df)
+}
+
+/// Load the `--segments` column-prefix groups (`head_x`/`head_y`/`head_z`
+/// plus the shared `t`) from one file, each normalized into its own
+/// x/y/z/t trajectory named after its prefix.
+pub async fn load_segments(config: &Config) -> Result<Vec<(String, DataFrame)>, TrajViewerError> {
+    let df = read_input(&config.filekey, config).await?;
+
+    let mut segments = Vec::with_capacity(config.segments.len());
+    for prefix in &config.segments {
+        let names = [
+            format!("{prefix}_x"),
+            format!("{prefix}_y"),
+            format!("{prefix}_z"),
+        ];
+        let mut seg = df.select(&names).map_err(|_| {
+            TrajViewerError::InvalidConfig(format!(
+                "--segments `{prefix}` needs columns `{prefix}_x`, `{prefix}_y`, `{prefix}_z`"
+            ))
+        })?;
+        for (from, to) in names.iter().zip(TRAJ_COLUMNS) {
+            seg.rename(from, to)?;
+        }
+        seg.with_column(df.column("t")?.clone())?;
+
+        let mut seg = normalize(seg, config)?;
+        align_time(&mut seg, &config.filekey, config)?;
+        apply_origin(&mut seg, &config.filekey, config)?;
+        segments.push((prefix.clone(), seg));
+    }
+    Ok(segments)
+}
+
+/// The trajectory columns plus any present `--keep-columns`, warning about
+/// requested extras the file does not have (usually a typo).
+fn selected_columns(df: &DataFrame, config: &Config) -> Vec<String> {
+    let mut columns: Vec<String> = TRAJ_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let extras = config
+        .confidence_col
+        .iter()
+        .chain(&config.label_col)
+        .chain(&config.color_by_column);
+    for name in extras.chain(&config.keep_columns) {
+        if columns.iter().any(|c| c == name) {
+            continue;
+        }
+        if df.column(name).is_ok() {
+            columns.push(name.clone());
+        } else {
+            eprintln!("warning: requested column `{name}` not found in input");
+        }
+    }
+    columns
+}
+
+/// Catch the silent failure mode of locale CSVs: a column that parsed but
+/// came out entirely null usually means the decimal separator was wrong.
+fn check_parsed_columns(df: &DataFrame, config: &Config) -> Result<(), TrajViewerError> {
+    if config.decimal_comma || df.height() == 0 {
+        return Ok(());
+    }
+    for series in df.get_columns() {
+        if series.null_count() == series.len() {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "column `{}` parsed entirely as null; if the file uses comma \
+                 decimals try --decimal-comma",
+                series.name()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Drop samples whose `--confidence-col` value is null or below
+/// `--min-confidence`, before the trajectory columns are selected. Removing
+/// the rows (rather than nulling them) makes the trail bridge the gap
+/// instead of jumping through unreliable coordinates.
+fn filter_confidence(df: DataFrame, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let (Some(name), Some(min)) = (&config.confidence_col, config.min_confidence) else {
+        return Ok(df);
+    };
+    let col = df.column(name).map_err(|_| {
+        TrajViewerError::InvalidConfig(format!(
+            "--confidence-col column `{name}` not found in input"
+        ))
+    })?;
+    let values = col.cast(&DataType::Float64)?;
+    let mask: BooleanChunked = values
+        .f64()?
+        .into_iter()
+        .map(|v| v.is_some_and(|v| v >= min))
+        .collect();
+
+    let kept = df.filter(&mask)?;
+    if kept.height() == 0 {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--min-confidence {min} drops every sample"
+        )));
+    }
+    if config.verbose {
+        println!("confidence filter kept {}/{} samples", kept.height(), df.height());
+    }
+    Ok(kept)
+}
+
+/// Coerce string-typed coordinate columns to Float64 before they hit the
+/// ndarray conversion, reporting exactly which column and sample failed
+/// instead of an opaque type-mismatch error. `--unit-suffix` strips a
+/// trailing unit (e.g. `12cm`) from each value first.
+fn coerce_numeric(df: &mut DataFrame, config: &Config) -> Result<(), TrajViewerError> {
+    for name in TRAJ_COLUMNS {
+        let col = df.column(name)?;
+        if col.dtype() != &DataType::Utf8 {
+            continue;
+        }
+        // Datetime strings in `t` are handled by `coerce_datetime_t`.
+        if name == "t" && config.t_is_datetime {
+            continue;
+        }
+
+        let strings = col.utf8()?.clone();
+        let mut values: Vec<Option<f64>> = Vec::with_capacity(strings.len());
+        for (i, v) in strings.into_iter().enumerate() {
+            let Some(s) = v else {
+                values.push(None);
+                continue;
+            };
+            let trimmed = s.trim();
+            let trimmed = match &config.unit_suffix {
+                Some(suffix) => trimmed.strip_suffix(suffix.as_str()).unwrap_or(trimmed).trim(),
+                None => trimmed,
+            };
+            match trimmed.parse::<f64>() {
+                Ok(f) => values.push(Some(f)),
+                Err(_) => {
+                    return Err(TrajViewerError::InvalidConfig(format!(
+                        "column `{name}` sample {i} is not numeric: `{s}`"
+                    )))
+                }
+            }
+        }
+        let parsed: Float64Chunked = values.into_iter().collect();
+        df.replace(name, parsed.into_series())?;
+    }
+    Ok(())
+}
+
+/// Select the trajectory columns and forward-fill null samples.
+pub fn normalize(df: DataFrame, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let df = filter_confidence(df, config)?;
+    let mut new_df = df.select(selected_columns(&df, config))?;
+    coerce_numeric(&mut new_df, config)?;
+    let mut new_df = new_df.fill_null(FillNullStrategy::Forward(None))?;
+    if config.warmup_frames > 0 {
+        new_df = new_df.slice(config.warmup_frames as i64, usize::MAX);
+    }
+    coerce_datetime_t(&mut new_df, config.t_is_datetime)?;
+    apply_flips(&mut new_df, config)?;
+    let new_df = ensure_time_sorted(new_df, config)?;
+    // The full debug table is noisy for large files; a shape-and-schema
+    // line covers the common case, `--verbose` restores the table.
+    if config.verbose {
+        println!("{new_df:?}");
+    } else {
+        let schema: Vec<String> = new_df
+            .iter()
+            .map(|s| format!("{} ({})", s.name(), s.dtype()))
+            .collect();
+        let (rows, cols) = new_df.shape();
+        println!("loaded {rows} rows x {cols} columns: {}", schema.join(", "));
+    }
+    Ok(new_df)
+}
+
+/// Sort by `t` when the timestamps are not already non-decreasing, so
+/// time stepping and resampling can rely on order. Scanning first keeps
+/// the common sorted case cheap; `--assume-sorted` skips even the scan.
+fn ensure_time_sorted(df: DataFrame, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    if config.assume_sorted {
+        return Ok(df);
+    }
+    let t = df.column("t")?.cast(&DataType::Float64)?;
+    let mut prev = f64::NEG_INFINITY;
+    let mut sorted = true;
+    for v in t.f64()?.into_no_null_iter() {
+        if v < prev {
+            sorted = false;
+            break;
+        }
+        prev = v;
+    }
+    if sorted {
+        return Ok(df);
+    }
+    if config.verbose {
+        println!("t is not monotonic; sorting {} samples by time", df.height());
+    }
+    Ok(df.sort(["t"], false, true)?)
+}
+
+/// Negate the flagged coordinate columns about zero, so users can correct
+/// handedness differences between tracking systems without editing CSVs.
+fn apply_flips(df: &mut DataFrame, config: &Config) -> Result<(), TrajViewerError> {
+    let flips = [
+        (config.flip_x, "x"),
+        (config.flip_y, "y"),
+        (config.flip_z, "z"),
+    ];
+    for (enabled, name) in flips {
+        if !enabled {
+            continue;
+        }
+        let col = df.column(name)?.cast(&DataType::Float64)?;
+        let negated = col.f64()?.apply(|v| v.map(|v| -v)).into_series();
+        df.replace(name, negated)?;
+    }
+    Ok(())
+}
+
+/// Convert a datetime `t` column to elapsed seconds since the first sample,
+/// so it survives the `Float64Type` ndarray conversion.
+///
+/// Datetime-typed columns are converted automatically; `--t-is-datetime`
+/// forces the conversion for string columns polars did not infer.
+fn coerce_datetime_t(df: &mut DataFrame, t_is_datetime: bool) -> Result<(), TrajViewerError> {
+    let t = df.column("t")?;
+
+    let t = if t_is_datetime && t.dtype() == &DataType::Utf8 {
+        t.cast(&DataType::Datetime(TimeUnit::Microseconds, None))?
+    } else {
+        t.clone()
+    };
+
+    let DataType::Datetime(unit, _) = t.dtype() else {
+        return Ok(());
+    };
+    let per_second = match unit {
+        TimeUnit::Nanoseconds => 1e9,
+        TimeUnit::Microseconds => 1e6,
+        TimeUnit::Milliseconds => 1e3,
+    };
+
+    let ints = t.cast(&DataType::Int64)?;
+    let ints = ints.i64()?;
+    let first = ints.get(0).unwrap_or(0);
+    let elapsed: Float64Chunked = ints
+        .into_iter()
+        .map(|v| v.map(|v| (v - first) as f64 / per_second))
+        .collect();
+    df.replace("t", elapsed.into_series())?;
+    Ok(())
+}
+
+/// Optional arena description from a `{filekey}.meta.json` sidecar file:
+/// axis bounds in data coordinates plus the measurement units.
+#[derive(serde::Deserialize, Debug, Clone, Default)]
+pub struct ArenaMeta {
+    pub xmin: Option<f64>,
+    pub xmax: Option<f64>,
+    pub ymin: Option<f64>,
+    pub ymax: Option<f64>,
+    pub zmin: Option<f64>,
+    pub zmax: Option<f64>,
+    pub units: Option<String>,
+    /// Reference time for `--align-time event`, in the units of `t`.
+    pub event_time: Option<f64>,
+}
+
+/// Read `{input_dir}/{filekey}.meta.json` when present. Parse failures are
+/// reported as a warning rather than aborting the run.
+pub fn load_meta(filekey: &str, config: &Config) -> Option<ArenaMeta> {
+    let path = Path::new(&config.input_dir).join(format!("{filekey}.meta.json"));
+    let text = std::fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&text) {
+        Ok(meta) => {
+            if config.verbose {
+                println!("using arena metadata from {}", path.display());
+            }
+            Some(meta)
+        }
+        Err(e) => {
+            eprintln!("warning: ignoring malformed {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Number of samples in the synthetic demo trajectory.
+const DEMO_SAMPLES: usize = 2000;
+
+/// Generate a deterministic noisy Lissajous trajectory for `--demo` runs
+/// and test fixtures. The same seed always yields the same data.
+pub fn demo_trajectory(seed: u64) -> Result<DataFrame, TrajViewerError> {
+    let mut rng = seed.max(1);
+    // xorshift64: enough randomness for visual noise, no extra dependency.
+    let mut noise = move || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        (rng as f64 / u64::MAX as f64 - 0.5) * 0.05
+    };
+
+    let mut xs = Vec::with_capacity(DEMO_SAMPLES);
+    let mut ys = Vec::with_capacity(DEMO_SAMPLES);
+    let mut zs = Vec::with_capacity(DEMO_SAMPLES);
+    let mut ts = Vec::with_capacity(DEMO_SAMPLES);
+    for i in 0..DEMO_SAMPLES {
+        let t = i as f64 * 0.02;
+        xs.push((1.3 * t).sin() + noise());
+        ys.push((0.7 * t).cos() + noise());
+        zs.push(1.0 + 0.5 * (0.4 * t).sin() + noise());
+        ts.push(t);
+    }
+
+    Ok(df!("x" => xs, "y" => ys, "z" => zs, "t" => ts)?)
+}
+
+fn read_csv_path(path: &Path, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    if config.decimal_comma {
+        return read_csv_bytes(std::fs::read(path)?, config);
+    }
+    let reader = CsvReader::from_path(path)?.has_header(true);
+    if config.mmap {
+        // `from_path` memory-maps the file; skipping the rechunk and using
+        // the low-memory path keeps the peak footprint close to the mapped
+        // pages instead of a full in-memory copy.
+        return Ok(reader.with_rechunk(false).low_memory(true).finish()?);
+    }
+    Ok(reader.finish()?)
+}
+
+fn read_csv_bytes(mut bytes: Vec<u8>, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    let mut delimiter = b',';
+    if config.decimal_comma {
+        // Locale exports use `;` as the delimiter and `,` for decimals;
+        // rewrite the decimals so the numeric parser accepts them.
+        for b in &mut bytes {
+            if *b == b',' {
+                *b = b'.';
+            }
+        }
+        delimiter = b';';
+    }
+    Ok(CsvReader::new(Cursor::new(bytes))
+        .has_header(true)
+        .with_separator(delimiter)
+        .finish()?)
+}
+
+fn read_parquet_path(path: &Path) -> Result<DataFrame, TrajViewerError> {
+    let file = std::fs::File::open(path)?;
+    Ok(ParquetReader::new(file).finish()?)
+}
+
+fn read_ipc_path(path: &Path) -> Result<DataFrame, TrajViewerError> {
+    let file = std::fs::File::open(path)?;
+    Ok(IpcReader::new(file).finish()?)
+}
+
+/// Read the trajectory by running `--query` (default
+/// `SELECT x, y, z, t FROM {filekey}`) against the `--sqlite` database.
+/// Integer and real columns load as `Float64`; columns holding any text
+/// load as strings, so datetime timestamps still go through the normal
+/// `t` parsing.
+fn read_sqlite(path: &Path, filekey: &str, config: &Config) -> Result<DataFrame, TrajViewerError> {
+    use rusqlite::types::Value;
+
+    if !path.exists() {
+        return Err(TrajViewerError::NotFound(path.to_path_buf()));
+    }
+    let conn =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let query = match &config.query {
+        Some(sql) => sql.clone(),
+        None => format!("SELECT x, y, z, t FROM {filekey}"),
+    };
+
+    let mut stmt = conn.prepare(&query)?;
+    let names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let mut columns: Vec<Vec<Value>> = vec![Vec::new(); names.len()];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        for (i, column) in columns.iter_mut().enumerate() {
+            column.push(row.get(i)?);
+        }
+    }
+
+    let series: Vec<Series> = names
+        .iter()
+        .zip(&columns)
+        .map(|(name, values)| {
+            if values.iter().any(|v| matches!(v, Value::Text(_))) {
+                let strings: Vec<Option<String>> = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Text(s) => Some(s.clone()),
+                        Value::Integer(i) => Some(i.to_string()),
+                        Value::Real(f) => Some(f.to_string()),
+                        Value::Null | Value::Blob(_) => None,
+                    })
+                    .collect();
+                Series::new(name, strings)
+            } else {
+                let floats: Vec<Option<f64>> = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Integer(i) => Some(*i as f64),
+                        Value::Real(f) => Some(*f),
+                        Value::Null | Value::Text(_) | Value::Blob(_) => None,
+                    })
+                    .collect();
+                Series::new(name, floats)
+            }
+        })
+        .collect();
+    Ok(DataFrame::new(series)?)
+}
+
+async fn download_s3(
+    bucket: &str,
+    key: &str,
+    max_bytes: Option<u64>,
+) -> Result<Vec<u8>, TrajViewerError> {
+    let sdk_config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let client = aws_sdk_s3::Client::new(&sdk_config);
+
+    // `--s3-max-bytes` guards against surprise costs from an unexpectedly
+    // large object; check the size up front rather than mid-download.
+    if let Some(limit) = max_bytes {
+        let head = client
+            .head_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| TrajViewerError::S3 {
+                key: key.to_string(),
+                message: e.to_string(),
+            })?;
+        let size = head.content_length().unwrap_or(0).max(0) as u64;
+        if size > limit {
+            return Err(TrajViewerError::S3 {
+                key: key.to_string(),
+                message: format!(
+                    "object is {size} bytes, above the --s3-max-bytes limit of {limit}"
+                ),
+            });
+        }
+    }
+
+    // A flaky connection can interrupt the body stream mid-download,
+    // leaving truncated bytes that later fail CSV parsing with a confusing
+    // error. Verify the byte count against the object's content length and
+    // retry the whole download once before giving up.
+    const DOWNLOAD_ATTEMPTS: usize = 2;
+    let mut last_short = (0u64, 0u64);
+    for attempt in 1..=DOWNLOAD_ATTEMPTS {
+        let res = client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| TrajViewerError::S3 {
+                key: key.to_string(),
+                message: e.to_string(),
+            })?;
+        let expected = res.content_length().unwrap_or(0).max(0) as u64;
+
+        let bytes = res
+            .body
+            .collect()
+            .await
+            .map_err(|e| TrajViewerError::S3 {
+                key: key.to_string(),
+                message: e.to_string(),
+            })?
+            .into_bytes();
+
+        if expected == 0 || bytes.len() as u64 == expected {
+            return Ok(bytes.to_vec());
+        }
+        last_short = (bytes.len() as u64, expected);
+        eprintln!(
+            "warning: download of {key} truncated ({} of {expected} bytes), attempt {attempt}/{DOWNLOAD_ATTEMPTS}",
+            bytes.len()
+        );
+    }
+
+    Err(TrajViewerError::TruncatedDownload {
+        key: key.to_string(),
+        got: last_short.0,
+        expected: last_short.1,
+    })
+}
+
+/// Best-effort write of the downloaded bytes next to where a local file
+/// would have been found, so the next run skips the download.
+fn cache_download(path: &PathBuf, bytes: &[u8], verbose: bool) {
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match std::fs::write(path, bytes) {
+        Ok(()) => {
+            if verbose {
+                println!("cached S3 download to {}", path.display());
+            }
+        }
+        Err(e) => eprintln!("warning: could not cache download to {}: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[tokio::test]
+    async fn header_only_csv_is_reported_as_empty() {
+        let dir = std::env::temp_dir().join("traj_viewer_empty_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("empty.csv"), "x,y,z,t\n").unwrap();
+
+        let config = Config::parse_from([
+            "traj_viewer",
+            "empty",
+            "--input-dir",
+            dir.to_str().unwrap(),
+        ]);
+        match load_csv(&config).await {
+            Err(TrajViewerError::Empty(path)) => {
+                assert!(path.ends_with("empty.csv"));
+            }
+            other => panic!("expected Empty error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn decimal_comma_csv_parses() {
+        let dir = std::env::temp_dir().join("traj_viewer_decimal_comma_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("locale.csv"),
+            "x;y;z;t\n1,5;2,25;0,5;0,0\n1,6;2,35;0,6;0,1\n",
+        )
+        .unwrap();
+
+        let config = Config::parse_from([
+            "traj_viewer",
+            "locale",
+            "--input-dir",
+            dir.to_str().unwrap(),
+            "--decimal-comma",
+        ]);
+        let df = load_csv(&config).await.unwrap();
+        assert_eq!(df.height(), 2);
+        let x = df.column("x").unwrap().f64().unwrap();
+        assert_eq!(x.get(0), Some(1.5));
+    }
+
+    #[test]
+    fn unit_suffix_is_stripped_and_bad_samples_are_reported() {
+        let df = df!(
+            "x" => ["1.0cm", "2.0cm"], "y" => ["0cm", "1cm"],
+            "z" => ["0cm", "0cm"], "t" => [0.0, 0.1],
+        )
+        .unwrap();
+        let config = Config::parse_from(["traj_viewer", "--unit-suffix", "cm"]);
+        let out = normalize(df, &config).unwrap();
+        assert_eq!(out.column("x").unwrap().f64().unwrap().get(1), Some(2.0));
+
+        let bad = df!(
+            "x" => ["1.0", "oops"], "y" => ["0", "1"],
+            "z" => ["0", "0"], "t" => [0.0, 0.1],
+        )
+        .unwrap();
+        let config = Config::parse_from(["traj_viewer"]);
+        match normalize(bad, &config) {
+            Err(TrajViewerError::InvalidConfig(msg)) => {
+                assert!(msg.contains("`x`") && msg.contains("sample 1"), "{msg}");
+            }
+            other => panic!("expected InvalidConfig, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn align_time_start_zeroes_the_origin() {
+        let mut df = df!(
+            "x" => [0.0, 1.0], "y" => [0.0, 1.0], "z" => [0.0, 1.0],
+            "t" => [10.0, 10.5],
+        )
+        .unwrap();
+        let config = Config::parse_from(["traj_viewer", "--align-time", "start"]);
+        align_time(&mut df, "traj", &config).unwrap();
+        let t = df.column("t").unwrap().f64().unwrap();
+        assert_eq!(t.get(0), Some(0.0));
+        assert_eq!(t.get(1), Some(0.5));
+    }
+
+    #[test]
+    fn unsorted_t_is_sorted_unless_asserted() {
+        let df = df!(
+            "x" => [2.0, 1.0, 3.0],
+            "y" => [0.0, 0.0, 0.0],
+            "z" => [0.0, 0.0, 0.0],
+            "t" => [1.0, 0.0, 2.0],
+        )
+        .unwrap();
+
+        let config = Config::parse_from(["traj_viewer", "traj"]);
+        let out = normalize(df.clone(), &config).unwrap();
+        let x = out.column("x").unwrap().f64().unwrap();
+        assert_eq!(x.get(0), Some(1.0));
+
+        let config = Config::parse_from(["traj_viewer", "traj", "--assume-sorted"]);
+        let out = normalize(df, &config).unwrap();
+        let x = out.column("x").unwrap().f64().unwrap();
+        assert_eq!(x.get(0), Some(2.0));
+    }
+
+    #[test]
+    fn part_time_is_rebased_when_the_clock_restarts() {
+        let acc = df!(
+            "x" => [0.0, 1.0, 2.0],
+            "t" => [0.0, 0.5, 1.0],
+        )
+        .unwrap();
+        let part = df!(
+            "x" => [3.0, 4.0],
+            "t" => [0.0, 0.5],
+        )
+        .unwrap();
+        let out = rebase_part_time(&acc, part).unwrap();
+        let t = out.column("t").unwrap().f64().unwrap();
+        assert_eq!(t.get(0), Some(1.5));
+        assert_eq!(t.get(1), Some(2.0));
+
+        // Already-continuous timestamps stay put.
+        let part = df!("x" => [5.0], "t" => [7.0]).unwrap();
+        let out = rebase_part_time(&acc, part).unwrap();
+        assert_eq!(out.column("t").unwrap().f64().unwrap().get(0), Some(7.0));
+    }
+
+    #[test]
+    fn sqlite_query_loads_numeric_columns() {
+        let dir = std::env::temp_dir().join(format!("traj_viewer_sqlite_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("traj.db");
+        let conn = rusqlite::Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE traj (x REAL, y REAL, z REAL, t REAL);
+             INSERT INTO traj VALUES (0.0, 0.0, 0.5, 0.0), (1.0, 0.25, 0.5, 1.0);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let config = Config::parse_from(["traj_viewer", "traj", "--sqlite", db.to_str().unwrap()]);
+        let df = read_sqlite(&db, "traj", &config).unwrap();
+        assert_eq!(df.height(), 2);
+        assert_eq!(df.get_column_names(), ["x", "y", "z", "t"]);
+        assert_eq!(df.column("y").unwrap().f64().unwrap().get(1), Some(0.25));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn df_to_xyzt_casts_integer_columns() {
+        let df = df!(
+            "x" => [0i64, 1],
+            "y" => [0i64, 0],
+            "z" => [0i64, 2],
+            "t" => [0i64, 1],
+        )
+        .unwrap();
+        let data = df_to_xyzt(&df).unwrap();
+        assert_eq!(data[[1, 0]], 1.0);
+        assert_eq!(data[[1, 2]], 2.0);
+        assert_eq!(data[[1, 3]], 1.0);
+    }
+
+    #[test]
+    fn df_to_xyzt_names_the_offending_column() {
+        let df = df!(
+            "x" => [1.0, 2.0],
+            "y" => [0.0, 0.0],
+            "z" => [0.0, f64::NAN],
+            "t" => [0.0, 1.0],
+        )
+        .unwrap();
+        let err = df_to_xyzt(&df).unwrap_err();
+        assert!(err.to_string().contains("`z`"), "{err}");
+
+        let df = df!("x" => [1.0], "z" => [0.0]).unwrap();
+        let err = df_to_xyzt(&df).unwrap_err();
+        assert!(err.to_string().contains("`y`"), "{err}");
+    }
+
+    #[test]
+    fn resample_uniform_interpolates_linearly() {
+        let df = df!(
+            "x" => [0.0, 1.0, 2.0], "y" => [0.0, 10.0, 20.0],
+            "z" => [0.0, 0.0, 0.0], "t" => [0.0, 1.0, 2.0],
+        )
+        .unwrap();
+        let out = resample_uniform(&df, 4.0).unwrap();
+        assert_eq!(out.height(), 9); // 0.0..=2.0 at 0.25s spacing
+        let x = out.column("x").unwrap().f64().unwrap();
+        let t = out.column("t").unwrap().f64().unwrap();
+        assert_eq!(t.get(1), Some(0.25));
+        assert_eq!(x.get(1), Some(0.25));
+        assert_eq!(x.get(6), Some(1.5));
+    }
+
+    #[test]
+    fn output_colliding_with_input_is_refused() {
+        let dir = std::env::temp_dir().join("traj_viewer_collision_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("coll.csv");
+        std::fs::write(&input, "x,y,z,t\n0,0,0,0\n").unwrap();
+
+        let config = Config::parse_from([
+            "traj_viewer",
+            "coll",
+            "--input-dir",
+            dir.to_str().unwrap(),
+            "--stats-out",
+            input.to_str().unwrap(),
+        ]);
+        match check_output_collision(&config) {
+            Err(TrajViewerError::InvalidConfig(msg)) => {
+                assert!(msg.contains("overwrite the input"), "{msg}");
+            }
+            other => panic!("expected InvalidConfig, got {other:?}"),
+        }
+
+        // A distinct output directory with no shared paths is fine.
+        let config = Config::parse_from([
+            "traj_viewer",
+            "coll",
+            "--input-dir",
+            dir.to_str().unwrap(),
+        ]);
+        check_output_collision(&config).unwrap();
+    }
+
+    #[test]
+    fn demo_trajectory_is_deterministic() {
+        let a = demo_trajectory(7).unwrap();
+        let b = demo_trajectory(7).unwrap();
+        assert_eq!(a.get_column_names(), vec!["x", "y", "z", "t"]);
+        assert_eq!(a.height(), DEMO_SAMPLES);
+        assert!(a.frame_equal(&b));
+    }
+}
@@ -0,0 +1,249 @@
+use clap::Parser;
+use polars::prelude::DataFrame;
+
+use traj_viewer::analysis::Stats;
+use traj_viewer::config::Config;
+use traj_viewer::error::TrajViewerError;
+use traj_viewer::events::{self, Event};
+use traj_viewer::{loader, render};
+
+fn main() {
+    let config = Config::parse();
+    let runtime = match build_runtime(&config) {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = runtime.block_on(try_main(&config)) {
+        events::emit(&config, Event::Error {
+            message: &e.to_string(),
+        });
+        events::log_run_summary(
+            &config,
+            0,
+            std::time::Duration::ZERO,
+            "-",
+            &format!("error: {e}"),
+        );
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Build the tokio runtime, honoring `--threads`: 1 means the
+/// current-thread runtime, N pins the worker-thread count, and the default
+/// is one worker per CPU core.
+fn build_runtime(config: &Config) -> Result<tokio::runtime::Runtime, TrajViewerError> {
+    let runtime = match config.threads {
+        Some(0) => {
+            return Err(TrajViewerError::InvalidConfig(
+                "--threads must be at least 1".into(),
+            ))
+        }
+        Some(1) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?,
+        Some(n) => tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(n)
+            .enable_all()
+            .build()?,
+        None => tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?,
+    };
+    Ok(runtime)
+}
+
+async fn try_main(config: &Config) -> Result<(), TrajViewerError> {
+    if config.selftest {
+        return selftest();
+    }
+
+    events::emit(config, Event::LoadStart {
+        filekey: &config.filekey,
+    });
+
+    if config.qc && !config.demo {
+        loader::check_output_collision(config)?;
+        let raw = loader::load_raw(&config.filekey, config).await?;
+        let report = traj_viewer::analysis::QcReport::compute(&raw, config)?;
+        report.print();
+        if let Some(path) = &config.stats_out {
+            render::ensure_parent_dir(path)?;
+            std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+        }
+        return Ok(());
+    }
+
+    if config.watch {
+        return watch(config).await;
+    }
+
+    let mut overlays: Vec<(String, DataFrame)> = Vec::new();
+    let df = if config.demo {
+        loader::demo_trajectory(config.seed)?
+    } else if !config.segments.is_empty() {
+        // The first segment is the main trajectory; the rest ride along
+        // as overlays, reusing the synchronized frame loop.
+        let mut segments = loader::load_segments(config).await?;
+        let (_, first) = segments.remove(0);
+        overlays.extend(segments);
+        first
+    } else {
+        loader::load_csv(config).await?
+    };
+    events::emit(config, Event::LoadEnd {
+        filekey: &config.filekey,
+        rows: df.height(),
+    });
+
+    for filekey in &config.overlay {
+        overlays.push((filekey.clone(), loader::load_filekey(filekey, config).await?));
+    }
+
+    if config.timeline {
+        let data = loader::df_to_xyzt(&df)?;
+        let ts: Vec<f64> = data.column(3).to_vec();
+        traj_viewer::analysis::TimelineReport::compute(&ts, config).print();
+    }
+
+    // `--dump-frame` reuses `--stats-out` for its own CSV, so skip the
+    // stats write that would otherwise race it for the same path.
+    if config.dry_run || (config.stats_out.is_some() && config.dump_frame.is_none()) {
+        let stats = compute_stats(&df)?;
+        if let Some(path) = &config.stats_out {
+            render::ensure_parent_dir(path)?;
+            std::fs::write(path, serde_json::to_string_pretty(&stats)?)?;
+        }
+        if config.dry_run {
+            println!("{stats:#?}");
+            return Ok(());
+        }
+    }
+
+    // On Ctrl-C the render loops finish the frame in progress and finalize
+    // the encoder, so the partial animation is still a valid file.
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            render::request_cancel();
+        }
+    });
+
+    let report = render::run(&df, &overlays, config)?;
+    events::emit(config, Event::FramesRendered {
+        frames: report.frames_written,
+    });
+    events::emit(config, Event::OutputWritten {
+        path: &report.output_path.display().to_string(),
+    });
+    events::log_run_summary(
+        config,
+        report.frames_written,
+        report.elapsed,
+        &report.output_path.display().to_string(),
+        "ok",
+    );
+    if config.verbose {
+        println!(
+            "wrote {} frame(s) to {}",
+            report.frames_written,
+            report.output_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Poll the input CSV and refresh the live PNG whenever rows are appended,
+/// until Ctrl-C. A missing or still-empty file is retried, not fatal.
+async fn watch(config: &Config) -> Result<(), TrajViewerError> {
+    let mut last_rows = 0usize;
+    println!(
+        "watching {}/{}.csv (Ctrl-C to stop)",
+        config.input_dir, config.filekey
+    );
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(
+                config.watch_interval_ms.max(1),
+            )) => {}
+        }
+
+        let df = match loader::load_watch_snapshot(&config.filekey, config) {
+            Ok(df) => df,
+            Err(TrajViewerError::NotFound(_) | TrajViewerError::Empty(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        if df.height() <= last_rows {
+            continue;
+        }
+        last_rows = df.height();
+
+        let path = render::render_live_frame(&df, config)?;
+        events::emit(config, Event::OutputWritten {
+            path: &path.display().to_string(),
+        });
+        if config.verbose {
+            println!("{last_rows} rows -> {}", path.display());
+        }
+    }
+}
+
+/// Render a synthetic trajectory through the full pipeline into a temp
+/// directory and verify the output is a valid GIF, so users can confirm
+/// their build (fonts, encoder) works before trusting it on real data.
+fn selftest() -> Result<(), TrajViewerError> {
+    let started = std::time::Instant::now();
+    let dir = std::env::temp_dir().join(format!("traj_viewer_selftest_{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let result = (|| -> Result<(), TrajViewerError> {
+        let config = Config::parse_from([
+            "traj_viewer",
+            "selftest",
+            "--output-dir",
+            dir.to_str().unwrap_or("."),
+            "--skip",
+            "100",
+        ]);
+        let df = loader::demo_trajectory(config.seed)?;
+        let report = render::run(&df, &[], &config)?;
+
+        let bytes = std::fs::read(&report.output_path)?;
+        if !(bytes.starts_with(b"GIF89a") || bytes.starts_with(b"GIF87a")) {
+            return Err(TrajViewerError::Drawing(format!(
+                "{} is not a valid GIF",
+                report.output_path.display()
+            )));
+        }
+        if report.frames_written == 0 {
+            return Err(TrajViewerError::Drawing("no frames were rendered".into()));
+        }
+        Ok(())
+    })();
+
+    let _ = std::fs::remove_dir_all(&dir);
+    match result {
+        Ok(()) => {
+            println!("selftest PASS in {:?}", started.elapsed());
+            Ok(())
+        }
+        Err(e) => {
+            println!("selftest FAIL in {:?}", started.elapsed());
+            Err(e)
+        }
+    }
+}
+
+fn compute_stats(df: &DataFrame) -> Result<Stats, TrajViewerError> {
+    let data = traj_viewer::loader::df_to_xyzt(df)?;
+    let mut xyz = Vec::with_capacity(data.nrows());
+    let mut ts = Vec::with_capacity(data.nrows());
+    for v in data.outer_iter() {
+        xyz.push((v[0], v[1], v[2]));
+        ts.push(v[3]);
+    }
+    Ok(Stats::compute(&xyz, &ts))
+}
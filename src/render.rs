@@ -0,0 +1,3488 @@
+//! Frame rendering: the animation loop, the 3D chart and the heatmap mode.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use linya::Progress;
+use ndarray::Array2;
+use plotters::coord::ranged3d::{Cartesian3d, ProjectionMatrix};
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use polars::prelude::*;
+
+use crate::analysis;
+use crate::config::{CaptionPos, Colormap, Config, Corner, Mode, ProjectionLayout, RenderStyle};
+use crate::error::TrajViewerError;
+use crate::loader::{self, ArenaMeta};
+
+/// A point in plot space: `(x, z, y)` of the data, since the vertical plot
+/// axis shows the data's `z` column.
+pub type Point3 = (f64, f64, f64);
+
+/// A `(min, max)` value range along one axis or scalar scale.
+type Range = (f64, f64);
+
+/// Axis bounds in plot space.
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct Bounds {
+    pub x: (f64, f64),
+    /// Vertical axis (the data's `z`).
+    pub y: (f64, f64),
+    /// Depth axis (the data's `y`).
+    pub z: (f64, f64),
+}
+
+impl Bounds {
+    /// The plane projections are drawn onto and the vertical axis minimum.
+    pub fn floor(&self) -> f64 {
+        self.y.0
+    }
+}
+
+/// Set by the Ctrl-C handler. The render loops check it between frames,
+/// so an interrupt finishes the current frame and finalizes the encoder,
+/// leaving a shorter but valid output instead of a corrupt one.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Ask the active render to stop after the frame in progress.
+pub fn request_cancel() {
+    CANCELLED.store(true, Ordering::Relaxed);
+}
+
+fn cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// What `run` did, for reporting and tests.
+#[derive(Debug)]
+pub struct RenderReport {
+    pub frames_written: usize,
+    pub output_path: PathBuf,
+    pub elapsed: Duration,
+}
+
+/// A prepared trajectory: plot-space points plus derived kinematics.
+struct TrajData {
+    name: String,
+    xyz: Vec<Point3>,
+    ts: Vec<f64>,
+    speeds: Vec<f64>,
+    accels: Vec<f64>,
+    /// Per-sample behavior labels from `--label-col`, when given.
+    labels: Option<Vec<Option<String>>>,
+    /// Per-sample values of the `--color-by-column` column, when given.
+    column_values: Option<Vec<f64>>,
+}
+
+impl TrajData {
+    fn new(name: String, df: &DataFrame, config: &Config) -> Result<TrajData, TrajViewerError> {
+        let (xyz, ts) = prepare(df, config)?;
+        let speeds = analysis::speeds(&xyz, &ts);
+        let accels = analysis::accels(&xyz, &ts);
+        let labels = extract_labels(df, config)?;
+        let column_values = extract_scalar_column(df, config)?;
+        Ok(TrajData {
+            name,
+            xyz,
+            ts,
+            speeds,
+            accels,
+            labels,
+            column_values,
+        })
+    }
+}
+
+/// Read the `--label-col` values, decimated in step with [`prepare`] so
+/// they stay aligned with the plotted samples.
+fn extract_labels(
+    df: &DataFrame,
+    config: &Config,
+) -> Result<Option<Vec<Option<String>>>, TrajViewerError> {
+    let Some(name) = &config.label_col else {
+        return Ok(None);
+    };
+    let col = df.column(name).map_err(|_| {
+        TrajViewerError::InvalidConfig(format!("--label-col column `{name}` not found in input"))
+    })?;
+    let strings = col.cast(&DataType::Utf8)?;
+    let mut labels: Vec<Option<String>> = strings
+        .utf8()?
+        .into_iter()
+        .map(|v| v.map(str::to_string))
+        .collect();
+    if let Some(every) = config.decimate {
+        if every > 1 {
+            labels = labels.into_iter().step_by(every).collect();
+        }
+    }
+    Ok(Some(labels))
+}
+
+/// Read the `--color-by-column` values as floats, decimated in step with
+/// [`prepare`] so they stay aligned with the plotted samples. Nulls map
+/// to the bottom of the scale rather than poisoning the range.
+fn extract_scalar_column(
+    df: &DataFrame,
+    config: &Config,
+) -> Result<Option<Vec<f64>>, TrajViewerError> {
+    let Some(name) = &config.color_by_column else {
+        return Ok(None);
+    };
+    let col = df.column(name).map_err(|_| {
+        TrajViewerError::InvalidConfig(format!(
+            "--color-by-column column `{name}` not found in input"
+        ))
+    })?;
+    let floats = col.cast(&DataType::Float64).map_err(|_| {
+        TrajViewerError::InvalidConfig(format!(
+            "--color-by-column column `{name}` is not numeric"
+        ))
+    })?;
+    let mut values: Vec<f64> = floats
+        .f64()?
+        .into_iter()
+        .map(|v| v.unwrap_or(f64::NAN))
+        .collect();
+    if let Some(every) = config.decimate {
+        if every > 1 {
+            values = values.into_iter().step_by(every).collect();
+        }
+    }
+    Ok(Some(values))
+}
+
+/// Distinct labels in order of first appearance, so colors stay stable
+/// across frames and runs.
+fn label_classes(labels: &[Option<String>]) -> Vec<String> {
+    let mut classes: Vec<String> = Vec::new();
+    for label in labels.iter().flatten() {
+        if !classes.iter().any(|c| c == label) {
+            classes.push(label.clone());
+        }
+    }
+    classes
+}
+
+/// Categorical color for a label; unlabeled (null) samples stay neutral.
+fn label_color(classes: &[String], label: Option<&String>) -> RGBAColor {
+    match label.and_then(|l| classes.iter().position(|c| c == l)) {
+        Some(i) => Palette99::pick(i).to_rgba(),
+        None => RGBColor(150, 150, 150).to_rgba(),
+    }
+}
+
+/// Everything `draw_frame` needs that does not change per frame.
+struct Scene<'a> {
+    title: &'a str,
+    xyz: &'a [Point3],
+    ts: &'a [f64],
+    speeds: &'a [f64],
+    accels: &'a [f64],
+    labels: Option<&'a [Option<String>]>,
+    /// Distinct labels, in first-appearance order, for colors and legend.
+    label_classes: Vec<String>,
+    /// Values and color range of the `--color-by-column` column.
+    column_values: Option<&'a [f64]>,
+    column_range: (f64, f64),
+    bounds: Bounds,
+    speed_range: (f64, f64),
+    accel_range: (f64, f64),
+    period: Option<f64>,
+    /// `--show-tortuosity`: (total turning angle in radians, straightness).
+    tortuosity: Option<(f64, f64)>,
+    overlays: &'a [TrajData],
+    keyframes: Vec<CameraKeyframe>,
+    units: Option<String>,
+    /// `--mark-time` events resolved to trajectory positions.
+    marks: Vec<(f64, Point3)>,
+    /// `--show-hull`: closed convex-hull outline on the floor plane.
+    hull: Vec<Point3>,
+    /// `--emit-camera`: per-frame camera samples collected while drawing.
+    camera_log: Option<Mutex<Vec<CameraSample>>>,
+    config: &'a Config,
+}
+
+/// One `--emit-camera` record: the projection parameters a frame was
+/// drawn with and the resulting matrix, pivot included.
+struct CameraSample {
+    frame_no: usize,
+    pitch: f64,
+    yaw: f64,
+    scale: f64,
+    matrix: [[f64; 4]; 4],
+}
+
+/// One keyframe of a scripted camera path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    pub frame: usize,
+    pub pitch: f64,
+    pub yaw: f64,
+    pub scale: f64,
+}
+
+/// Parse `--camera-keyframes`: either an inline `frame,pitch,yaw,scale;...`
+/// list or a path to a file with one keyframe per line.
+fn parse_keyframes(spec: &str) -> Result<Vec<CameraKeyframe>, TrajViewerError> {
+    let text = if Path::new(spec).exists() {
+        std::fs::read_to_string(spec)?.replace('\n', ";")
+    } else {
+        spec.to_string()
+    };
+
+    let mut keyframes = Vec::new();
+    for entry in text.split(';') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = entry
+            .split([',', ' '])
+            .filter(|p| !p.is_empty())
+            .collect();
+        let invalid = || {
+            TrajViewerError::InvalidConfig(format!(
+                "camera keyframe `{entry}` must be frame,pitch,yaw,scale"
+            ))
+        };
+        if parts.len() != 4 {
+            return Err(invalid());
+        }
+        keyframes.push(CameraKeyframe {
+            frame: parts[0].parse().map_err(|_| invalid())?,
+            pitch: parts[1].parse().map_err(|_| invalid())?,
+            yaw: parts[2].parse().map_err(|_| invalid())?,
+            scale: parts[3].parse().map_err(|_| invalid())?,
+        });
+    }
+    if keyframes.is_empty() {
+        return Err(TrajViewerError::InvalidConfig(
+            "--camera-keyframes contained no keyframes".into(),
+        ));
+    }
+    keyframes.sort_by_key(|k| k.frame);
+    Ok(keyframes)
+}
+
+/// Camera parameters `(pitch, yaw, scale)` at `frame_no`, linearly
+/// interpolated between keyframes and clamped at the ends.
+fn camera_at(keyframes: &[CameraKeyframe], frame_no: usize) -> (f64, f64, f64) {
+    let first = keyframes.first().expect("keyframes are non-empty");
+    let last = keyframes.last().expect("keyframes are non-empty");
+    if frame_no <= first.frame {
+        return (first.pitch, first.yaw, first.scale);
+    }
+    if frame_no >= last.frame {
+        return (last.pitch, last.yaw, last.scale);
+    }
+    for w in keyframes.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        if frame_no >= a.frame && frame_no <= b.frame {
+            let span = (b.frame - a.frame).max(1) as f64;
+            let f = (frame_no - a.frame) as f64 / span;
+            return (
+                a.pitch + f * (b.pitch - a.pitch),
+                a.yaw + f * (b.yaw - a.yaw),
+                a.scale + f * (b.scale - a.scale),
+            );
+        }
+    }
+    (last.pitch, last.yaw, last.scale)
+}
+
+/// Build the per-trajectory scene (bounds, color scale, period) for `data`.
+fn build_scene<'a>(data: &'a TrajData, overlays: &'a [TrajData], config: &'a Config) -> Result<Scene<'a>, TrajViewerError> {
+    let meta = loader::load_meta(&data.name, config);
+    let bounds = compute_bounds(&data.xyz, config, meta.as_ref());
+    let keyframes = match &config.camera_keyframes {
+        Some(spec) => parse_keyframes(spec)?,
+        None => Vec::new(),
+    };
+    let speed_max = config
+        .speed_max
+        .unwrap_or_else(|| data.speeds.iter().cloned().fold(0.0, f64::max));
+    let accel_max = config
+        .accel_max
+        .unwrap_or_else(|| data.accels.iter().cloned().fold(0.0, f64::max));
+    let (speed_range, accel_range) = match &config.shared_scale_file {
+        Some(path) => shared_scale(path, speed_max, accel_max, config)?,
+        None => ((0.0, speed_max), (0.0, accel_max)),
+    };
+    let column_range = match (&config.column_range, &data.column_values) {
+        (Some(range), _) if range[0] >= range[1] => {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "--column-range needs lo < hi, got {} {}",
+                range[0], range[1]
+            )))
+        }
+        (Some(range), _) => (range[0], range[1]),
+        (None, Some(values)) => {
+            let finite = values.iter().cloned().filter(|v| v.is_finite());
+            let lo = finite.clone().fold(f64::INFINITY, f64::min);
+            let hi = finite.fold(f64::NEG_INFINITY, f64::max);
+            if lo.is_finite() && hi > lo {
+                (lo, hi)
+            } else {
+                (0.0, 1.0)
+            }
+        }
+        (None, None) => (0.0, 1.0),
+    };
+    let zs: Vec<f64> = data.xyz.iter().map(|p| p.1).collect();
+    let period = if config.show_period {
+        analysis::get_period(&data.ts, &zs)
+    } else {
+        None
+    };
+    let tortuosity = config.show_tortuosity.then(|| {
+        (
+            analysis::turning_angles(&data.xyz).iter().sum(),
+            analysis::straightness(&data.xyz),
+        )
+    });
+    // `--show-hull`: hull of the ground-plane (plot x/z) positions,
+    // closed and pinned to the floor for drawing.
+    let hull = if config.show_hull {
+        let flat: Vec<(f64, f64)> = data.xyz.iter().map(|p| (p.0, p.2)).collect();
+        let mut hull: Vec<Point3> = analysis::convex_hull(&flat)
+            .into_iter()
+            .map(|(x, depth)| (x, bounds.floor(), depth))
+            .collect();
+        if let Some(&first) = hull.first() {
+            hull.push(first);
+        }
+        hull
+    } else {
+        Vec::new()
+    };
+    Ok(Scene {
+        title: &data.name,
+        xyz: &data.xyz,
+        ts: &data.ts,
+        speeds: &data.speeds,
+        accels: &data.accels,
+        labels: data.labels.as_deref(),
+        label_classes: data.labels.as_deref().map(label_classes).unwrap_or_default(),
+        column_values: data.column_values.as_deref(),
+        column_range,
+        bounds,
+        speed_range: (speed_range.0, speed_range.1.max(f64::EPSILON)),
+        accel_range: (accel_range.0, accel_range.1.max(f64::EPSILON)),
+        period,
+        tortuosity,
+        overlays,
+        keyframes,
+        units: meta.and_then(|m| m.units),
+        marks: config
+            .mark_times
+            .iter()
+            .filter_map(|&t| position_at_time(&data.xyz, &data.ts, t).map(|p| (t, p)))
+            .collect(),
+        hull,
+        camera_log: config.emit_camera.as_ref().map(|_| Mutex::new(Vec::new())),
+        config,
+    })
+}
+
+/// `--validate-bounds`: refuse to render when samples fall outside the
+/// `--fixed-bounds` box (they would be drawn off-frame silently),
+/// reporting how many and suggesting bounds that cover the data.
+fn validate_bounds(scene: &Scene) -> Result<(), TrajViewerError> {
+    let b = scene.bounds;
+    let inside = |v: f64, (lo, hi): (f64, f64)| v >= lo && v <= hi;
+    let outside = scene
+        .xyz
+        .iter()
+        .filter(|p| !(inside(p.0, b.x) && inside(p.1, b.y) && inside(p.2, b.z)))
+        .count();
+    if outside == 0 {
+        return Ok(());
+    }
+
+    let mut lo = (f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut hi = (f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for p in scene.xyz {
+        lo = (lo.0.min(p.0), lo.1.min(p.1), lo.2.min(p.2));
+        hi = (hi.0.max(p.0), hi.1.max(p.1), hi.2.max(p.2));
+    }
+    // Suggest in the data order `--fixed-bounds` expects.
+    let (ylo, yhi, zlo, zhi) = if scene.config.no_axis_swap {
+        (lo.1, hi.1, lo.2, hi.2)
+    } else {
+        (lo.2, hi.2, lo.1, hi.1)
+    };
+    Err(TrajViewerError::InvalidConfig(format!(
+        "{outside}/{} samples fall outside --fixed-bounds; the data extent \
+         suggests --fixed-bounds {:.3} {:.3} {ylo:.3} {yhi:.3} {zlo:.3} {zhi:.3}",
+        scene.xyz.len(),
+        lo.0,
+        hi.0
+    )))
+}
+
+/// Merge this run's kinematic maxima into the `--shared-scale-file` JSON
+/// and return the merged ranges, so every trajectory in a batch is
+/// colored on one scale. Pre-filling the file pins the scale outright; a
+/// first pass over the batch computes the global maxima for a second,
+/// fully comparable pass.
+fn shared_scale(
+    path: &Path,
+    speed_max: f64,
+    accel_max: f64,
+    config: &Config,
+) -> Result<(Range, Range), TrajViewerError> {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct SharedScale {
+        speed_min: f64,
+        speed_max: f64,
+        accel_min: f64,
+        accel_max: f64,
+    }
+
+    use fs2::FileExt;
+    use std::io::{Read, Seek, Write};
+
+    ensure_parent_dir(path)?;
+    // Hold an exclusive lock across the read-merge-write so concurrent
+    // batch invocations don't drop each other's maxima, matching the
+    // `--log-file` locking in `events::log_run_summary`.
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(path)?;
+    file.lock_exclusive()?;
+    let result = (|| -> Result<(Range, Range), TrajViewerError> {
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let mut scale = if text.trim().is_empty() {
+            SharedScale {
+                speed_min: 0.0,
+                speed_max: 0.0,
+                accel_min: 0.0,
+                accel_max: 0.0,
+            }
+        } else {
+            serde_json::from_str::<SharedScale>(&text)?
+        };
+        scale.speed_max = scale.speed_max.max(speed_max);
+        scale.accel_max = scale.accel_max.max(accel_max);
+
+        file.set_len(0)?;
+        file.seek(std::io::SeekFrom::Start(0))?;
+        file.write_all(serde_json::to_string_pretty(&scale)?.as_bytes())?;
+        if config.verbose {
+            println!(
+                "shared scale: speed {:?}, accel {:?}",
+                (scale.speed_min, scale.speed_max),
+                (scale.accel_min, scale.accel_max)
+            );
+        }
+        Ok((
+            (scale.speed_min, scale.speed_max),
+            (scale.accel_min, scale.accel_max),
+        ))
+    })();
+    let _ = file.unlock();
+    result
+}
+
+/// Trajectory position at time `t`, linearly interpolated between the two
+/// surrounding samples. `None` when `t` is outside the recording.
+fn position_at_time(xyz: &[Point3], ts: &[f64], t: f64) -> Option<Point3> {
+    let idx = ts.iter().position(|&v| v >= t)?;
+    if idx == 0 {
+        return (ts[0] == t).then(|| xyz[0]);
+    }
+    let (t0, t1) = (ts[idx - 1], ts[idx]);
+    let f = if t1 > t0 { (t - t0) / (t1 - t0) } else { 0.0 };
+    let (a, b) = (xyz[idx - 1], xyz[idx]);
+    Some((
+        a.0 + f * (b.0 - a.0),
+        a.1 + f * (b.1 - a.1),
+        a.2 + f * (b.2 - a.2),
+    ))
+}
+
+/// Render the trajectory according to the configured mode.
+pub fn run(
+    df: &DataFrame,
+    overlays: &[(String, DataFrame)],
+    config: &Config,
+) -> Result<RenderReport, TrajViewerError> {
+    let started = Instant::now();
+
+    if config.view_scale <= 0.0 {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--view-scale must be positive, got {}",
+            config.view_scale
+        )));
+    }
+
+    if let Some(span) = config.trail_seconds {
+        if span <= 0.0 {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "--trail-seconds must be positive, got {span}"
+            )));
+        }
+    }
+
+    // `--target-duration` is just a friendlier way to choose `--skip`:
+    // derive it from the frame delay and re-run with the explicit value.
+    if let Some(duration) = config.target_duration {
+        if duration <= 0.0 {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "--target-duration must be positive, got {duration}"
+            )));
+        }
+        let frames = (duration / config.secs.max(f64::EPSILON)).round().max(1.0);
+        // `saturating_sub`: `--start` past `--end-frame` is an empty range,
+        // which `frame_indices` already renders as zero frames.
+        let n = config
+            .end_frame
+            .unwrap_or(df.height())
+            .min(df.height())
+            .saturating_sub(config.start.min(df.height()));
+        let skip = (n as f64 / frames).ceil().max(1.0) as usize;
+        if n > 0 {
+            println!("target duration {duration}s: {} frames, skip {skip}", frames as usize);
+        }
+
+        let mut derived = config.clone();
+        derived.target_duration = None;
+        derived.skip = skip;
+        return run(df, overlays, &derived);
+    }
+
+    loader::check_output_collision(config)?;
+
+    // A missing output directory would otherwise surface as an opaque
+    // backend path error on the first frame.
+    std::fs::create_dir_all(&config.output_dir).map_err(|e| {
+        TrajViewerError::InvalidConfig(format!(
+            "cannot create output dir `{}`: {e}",
+            config.output_dir
+        ))
+    })?;
+
+    if let Some(chunk_size) = config.chunk_size {
+        return run_chunked(df, overlays, config, chunk_size, started);
+    }
+
+    // `--max-memory-mb`: bail out with an actionable message instead of
+    // letting the OOM killer end the process opaquely. Chunked runs are
+    // checked per chunk, after the dispatch above.
+    if let Some(limit_mb) = config.max_memory_mb {
+        let samples: usize = std::iter::once(df)
+            .chain(overlays.iter().map(|(_, df)| df))
+            .map(|df| df.height() * df.width() * 8)
+            .sum();
+        // The drawing buffer, plus one more for the cached background or
+        // the quantizer's RGBA copy.
+        let buffers = (config.width * config.height * 3) as usize * 2;
+        let estimate_mb = (samples + buffers) / (1024 * 1024);
+        if estimate_mb > limit_mb {
+            return Err(TrajViewerError::InvalidConfig(format!(
+                "estimated working set of {estimate_mb} MB exceeds --max-memory-mb \
+                 {limit_mb}; reduce it with --decimate or render in parts with \
+                 --chunk-size"
+            )));
+        }
+    }
+
+    let main = TrajData::new(config.filekey.clone(), df, config)?;
+    let overlays: Vec<TrajData> = overlays
+        .iter()
+        .map(|(name, df)| TrajData::new(name.clone(), df, config))
+        .collect::<Result<_, TrajViewerError>>()?;
+
+    // Chapter separators only exist in the animated-GIF frame loops; flag
+    // the combination instead of dropping them silently. Checked before
+    // the single-image dispatches below, which return early.
+    let animated = matches!(config.mode, Mode::Gif | Mode::Accumulate)
+        && config.grid.is_none()
+        && config.dump_frame.is_none()
+        && config.contact_sheet.is_none()
+        && config.goto_time.is_none();
+    if !config.chapter_markers.is_empty() && !animated {
+        eprintln!("warning: --chapter-markers only applies to the gif and accumulate modes; ignoring");
+    }
+
+    if let Some(grid) = &config.grid {
+        let (rows, cols) = parse_grid(grid)?;
+        let mut cells = vec![main];
+        cells.extend(overlays);
+        let report = render_grid(&cells, rows, cols, config, started)?;
+        println!("Processing Time: {:?}", report.elapsed);
+        return Ok(report);
+    }
+
+    let scene = build_scene(&main, &overlays, config)?;
+
+    if config.validate_bounds {
+        validate_bounds(&scene)?;
+    }
+
+    if let Some(frame_no) = config.dump_frame {
+        return dump_frame(&scene, frame_no, started);
+    }
+
+    if let Some(spec) = &config.contact_sheet {
+        let report = render_contact_sheet(&scene, spec, started)?;
+        println!("Processing Time: {:?}", report.elapsed);
+        return Ok(report);
+    }
+
+    if let Some(t) = config.goto_time {
+        let report = render_goto_time(&scene, t, started)?;
+        println!("Processing Time: {:?}", report.elapsed);
+        return Ok(report);
+    }
+
+    let report = match config.mode {
+        Mode::Heatmap => render_heatmap(&scene, started)?,
+        Mode::Gif | Mode::Accumulate | Mode::PngSequence if config.preview => {
+            render_preview(&scene, started)?
+        }
+        // `--delay-from-time` needs per-frame delays, which only the
+        // quantized encoder path can write.
+        Mode::Gif | Mode::Accumulate
+            if config.gif_colors.is_some()
+                || config.gif_scale.is_some()
+                || config.delay_from_time =>
+        {
+            render_gif_quantized(&scene, started, None)?
+        }
+        Mode::Gif | Mode::Accumulate => render_gif(&scene, started)?,
+        Mode::PngSequence => render_png_sequence(&scene, started)?,
+        Mode::Density3d => render_density3d(&scene, started)?,
+    };
+
+    if cancelled() {
+        println!(
+            "interrupted: {} frame(s) written, output finalized",
+            report.frames_written
+        );
+        println!("Processing Time: {:?}", report.elapsed);
+        return Ok(report);
+    }
+
+    if let Some(path) = &config.occupancy_out {
+        ensure_parent_dir(path)?;
+        Occupancy::compute(&scene).write_csv(path)?;
+        if config.verbose {
+            println!("wrote occupancy histogram to {}", path.display());
+        }
+    }
+    if let Some(path) = &config.emit_params {
+        emit_params(path, &scene, &report)?;
+    }
+    if let Some(path) = &config.emit_camera {
+        write_camera_log(path, &scene)?;
+    }
+    if config.export_projections {
+        export_projections(&scene)?;
+    }
+
+    println!("Processing Time: {:?}", report.elapsed);
+    Ok(report)
+}
+
+/// Per-frame context handed to the [`render_trajectory_with_hook`]
+/// callback alongside the rendered image.
+pub struct FrameContext {
+    /// Zero-based frame number in the output animation.
+    pub frame_no: usize,
+    /// Timestamp of the frame's leading sample, in data time units.
+    pub t0: f64,
+    /// Camera pitch the frame was projected with, in radians.
+    pub pitch: f64,
+    /// Camera yaw the frame was projected with, in radians.
+    pub yaw: f64,
+    /// Camera zoom scale the frame was projected with.
+    pub scale: f64,
+}
+
+/// Callback type for [`render_trajectory_with_hook`]: mutate the
+/// full-resolution frame in place before it is downscaled and encoded.
+pub type FrameHook<'a> = dyn FnMut(&mut image::RgbImage, &FrameContext) + 'a;
+
+/// Render the trajectory GIF like [`run`], handing every frame to `hook`
+/// before encoding — the escape hatch for watermarks, custom overlays or
+/// filters the built-in flags don't cover. Frames always go through the
+/// internal quantizing encoder, so `--gif-colors`, `--gif-scale` and
+/// `--delay-from-time` still apply.
+pub fn render_trajectory_with_hook(
+    df: &DataFrame,
+    config: &Config,
+    mut hook: impl FnMut(&mut image::RgbImage, &FrameContext),
+) -> Result<RenderReport, TrajViewerError> {
+    let started = Instant::now();
+    loader::check_output_collision(config)?;
+    std::fs::create_dir_all(&config.output_dir).map_err(|e| {
+        TrajViewerError::InvalidConfig(format!(
+            "cannot create output dir `{}`: {e}",
+            config.output_dir
+        ))
+    })?;
+    let main = TrajData::new(config.filekey.clone(), df, config)?;
+    let scene = build_scene(&main, &[], config)?;
+    render_gif_quantized(&scene, started, Some(&mut hook))
+}
+
+/// Dump the raw numbers behind frame `frame_no` (`--dump-frame`): every
+/// sample in its trail window with per-sample speed, plus the camera
+/// parameters the frame would be drawn with. Written as CSV to
+/// `--stats-out` when given, otherwise to stdout, so a visual artifact
+/// can be traced to its data without re-deriving indices by hand.
+fn dump_frame(
+    scene: &Scene,
+    frame_no: usize,
+    started: Instant,
+) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    // `filtered_leads`, not `frame_indices`: frame numbers must match the
+    // animation, which drops frames failing `--filter-expr`.
+    let leads = filtered_leads(scene)?;
+    let lead = *leads.get(frame_no).ok_or_else(|| {
+        TrajViewerError::InvalidConfig(format!(
+            "--dump-frame {frame_no} is out of range; this run renders {} frames",
+            leads.len()
+        ))
+    })?;
+    let trail_len = trail_length(scene, lead);
+    let from = lead.saturating_sub(trail_len);
+
+    let (pitch, yaw, scale) = if scene.keyframes.is_empty() {
+        (0.25, yaw_at(frame_no, config), config.view_scale)
+    } else {
+        camera_at(&scene.keyframes, frame_no)
+    };
+
+    let mut out = format!(
+        "# frame {frame_no}: lead sample {lead}, trail window {from}..={lead}\n\
+         # camera pitch={pitch} yaw={yaw} scale={scale}\n\
+         sample,x,y,z,t,speed\n"
+    );
+    for i in from..=lead {
+        let p = scene.xyz[i];
+        // Back to data coordinates: the plot's vertical axis is data z
+        // unless `--no-axis-swap` kept the natural order.
+        let (y, z) = if config.no_axis_swap { (p.1, p.2) } else { (p.2, p.1) };
+        out.push_str(&format!(
+            "{i},{},{y},{z},{},{}\n",
+            p.0, scene.ts[i], scene.speeds[i]
+        ));
+    }
+
+    let output_path = match &config.stats_out {
+        Some(path) => {
+            ensure_parent_dir(path)?;
+            std::fs::write(path, out)?;
+            path.clone()
+        }
+        None => {
+            print!("{out}");
+            PathBuf::from("<stdout>")
+        }
+    };
+    Ok(RenderReport {
+        frames_written: 0,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Write the resolved run parameters for `--emit-params`: the full
+/// configuration plus the derived values (auto-computed bounds, effective
+/// frame count, yaw range) a later run needs to reproduce this one exactly.
+fn emit_params(
+    path: &Path,
+    scene: &Scene,
+    report: &RenderReport,
+) -> Result<(), TrajViewerError> {
+    #[derive(serde::Serialize)]
+    struct ResolvedParams<'a> {
+        config: &'a Config,
+        bounds: Bounds,
+        floor: f64,
+        frames_written: usize,
+        yaw_range: (f64, f64),
+    }
+
+    let yaw_range = if scene.keyframes.is_empty() {
+        // The default camera oscillates +/- 0.5 around `--start-yaw`.
+        (scene.config.start_yaw - 0.5, scene.config.start_yaw + 0.5)
+    } else {
+        scene.keyframes.iter().fold(
+            (f64::INFINITY, f64::NEG_INFINITY),
+            |(lo, hi), kf| (lo.min(kf.yaw), hi.max(kf.yaw)),
+        )
+    };
+
+    ensure_parent_dir(path)?;
+    let params = ResolvedParams {
+        config: scene.config,
+        bounds: scene.bounds,
+        floor: scene.bounds.floor(),
+        frames_written: report.frames_written,
+        yaw_range,
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&params)?)?;
+    Ok(())
+}
+
+/// Write the `--emit-camera` CSV: per frame the projection parameters and
+/// the row-major 4x4 matrix the frame was actually drawn with.
+fn write_camera_log(path: &Path, scene: &Scene) -> Result<(), TrajViewerError> {
+    let Some(log) = &scene.camera_log else {
+        return Ok(());
+    };
+    let mut samples = log
+        .lock()
+        .map_err(|_| TrajViewerError::Drawing("camera log poisoned".into()))?;
+    samples.sort_by_key(|s| s.frame_no);
+
+    let mut out = String::from("frame,pitch,yaw,scale");
+    for r in 0..4 {
+        for c in 0..4 {
+            out.push_str(&format!(",m{r}{c}"));
+        }
+    }
+    out.push('\n');
+    for s in samples.iter() {
+        out.push_str(&format!("{},{},{},{}", s.frame_no, s.pitch, s.yaw, s.scale));
+        for row in &s.matrix {
+            for v in row {
+                out.push_str(&format!(",{v}"));
+            }
+        }
+        out.push('\n');
+    }
+
+    ensure_parent_dir(path)?;
+    std::fs::write(path, out)?;
+    if scene.config.verbose {
+        println!("wrote {} camera sample(s) to {}", samples.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Render very long trajectories in row chunks, producing one output per
+/// chunk. Each chunk starts `--trail` samples before its nominal range so
+/// trails are continuous across chunk boundaries.
+fn run_chunked(
+    df: &DataFrame,
+    overlays: &[(String, DataFrame)],
+    config: &Config,
+    chunk_size: usize,
+    started: Instant,
+) -> Result<RenderReport, TrajViewerError> {
+    if chunk_size == 0 {
+        return Err(TrajViewerError::InvalidConfig(
+            "--chunk-size must be positive".into(),
+        ));
+    }
+
+    let n = df.height();
+    let mut part = 1;
+    let mut chunk_start = 0;
+    let mut frames_written = 0;
+    while chunk_start < n {
+        let slice_start = chunk_start.saturating_sub(config.trail);
+        let slice_len = (chunk_start - slice_start) + chunk_size;
+        let slice = df.slice(slice_start as i64, slice_len);
+
+        let mut part_config = config.clone();
+        part_config.filekey = format!("{}_part{part}", config.filekey);
+        part_config.chunk_size = None;
+        part_config.start = 0;
+        part_config.end_frame = None;
+
+        if config.verbose {
+            println!(
+                "chunk {part}: samples {slice_start}..{}",
+                (slice_start + slice.height()).min(n)
+            );
+        }
+        let report = run(&slice, overlays, &part_config)?;
+        frames_written += report.frames_written;
+
+        chunk_start += chunk_size;
+        part += 1;
+    }
+
+    Ok(RenderReport {
+        frames_written,
+        output_path: PathBuf::from(&config.output_dir),
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Convert the normalized DataFrame into plot-space points and timestamps,
+/// applying decimation, smoothing and normalization.
+fn prepare(df: &DataFrame, config: &Config) -> Result<(Vec<Point3>, Vec<f64>), TrajViewerError> {
+    // Kept extra columns may be non-numeric; only x/y/z/t feed the plot.
+    let data: Array2<f64> = loader::df_to_xyzt(df)?;
+
+    let mut xyz: Vec<Point3> = Vec::with_capacity(data.nrows());
+    let mut ts: Vec<f64> = Vec::with_capacity(data.nrows());
+    for v in data.outer_iter() {
+        // The vertical plot axis is the data's z column (height), unless
+        // `--no-axis-swap` asks for the natural column order.
+        if config.no_axis_swap {
+            xyz.push((v[0], v[1], v[2]));
+        } else {
+            xyz.push((v[0], v[2], v[1]));
+        }
+        ts.push(v[3]);
+    }
+
+    if let Some(every) = config.decimate {
+        if every > 1 {
+            xyz = xyz.iter().step_by(every).copied().collect();
+            ts = ts.iter().step_by(every).copied().collect();
+        }
+    }
+
+    if let Some(window) = config.smooth {
+        if window > 1 {
+            let xs = analysis::moving_average(&component(&xyz, 0), window);
+            let ys = analysis::moving_average(&component(&xyz, 1), window);
+            let zs = analysis::moving_average(&component(&xyz, 2), window);
+            xyz = (0..xs.len()).map(|i| (xs[i], ys[i], zs[i])).collect();
+        }
+    }
+
+    if config.normalize {
+        for axis in 0..3 {
+            let vals = component(&xyz, axis);
+            let lo = vals.iter().cloned().fold(f64::INFINITY, f64::min);
+            let hi = vals.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let span = (hi - lo).max(f64::EPSILON);
+            for p in xyz.iter_mut() {
+                let v = (get_axis(p, axis) - lo) / span;
+                set_axis(p, axis, v);
+            }
+        }
+    }
+
+    Ok((xyz, ts))
+}
+
+fn component(xyz: &[Point3], axis: usize) -> Vec<f64> {
+    xyz.iter().map(|p| get_axis(p, axis)).collect()
+}
+
+fn get_axis(p: &Point3, axis: usize) -> f64 {
+    match axis {
+        0 => p.0,
+        1 => p.1,
+        _ => p.2,
+    }
+}
+
+fn set_axis(p: &mut Point3, axis: usize, v: f64) {
+    match axis {
+        0 => p.0 = v,
+        1 => p.1 = v,
+        _ => p.2 = v,
+    }
+}
+
+fn compute_bounds(xyz: &[Point3], config: &Config, meta: Option<&ArenaMeta>) -> Bounds {
+    if let Some(b) = &config.fixed_bounds {
+        // Given in data coordinates: xmin xmax ymin ymax zmin zmax.
+        return if config.no_axis_swap {
+            Bounds {
+                x: (b[0], b[1]),
+                y: (b[2], b[3]),
+                z: (b[4], b[5]),
+            }
+        } else {
+            Bounds {
+                x: (b[0], b[1]),
+                y: (b[4], b[5]),
+                z: (b[2], b[3]),
+            }
+        };
+    }
+
+    let (mut xmin, mut xmax) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut ymin, mut ymax) = (f64::INFINITY, f64::NEG_INFINITY);
+    let (mut zmin, mut zmax) = (f64::INFINITY, f64::NEG_INFINITY);
+    for p in xyz {
+        xmin = xmin.min(p.0);
+        xmax = xmax.max(p.0);
+        ymin = ymin.min(p.1);
+        ymax = ymax.max(p.1);
+        zmin = zmin.min(p.2);
+        zmax = zmax.max(p.2);
+    }
+    let margin = |lo: f64, hi: f64| 0.05 * (hi - lo).max(1.0);
+
+    // The floor is where projections land and where the vertical axis
+    // starts: -1.0 by default, or estimated from the data when asked.
+    let floor = if config.detect_floor {
+        let verticals: Vec<f64> = xyz.iter().map(|p| p.1).collect();
+        let detected = analysis::percentile(&verticals, 0.05);
+        if config.verbose {
+            println!("detected floor at z = {detected:.3}");
+        }
+        detected
+    } else {
+        -1.0
+    };
+
+    let auto = Bounds {
+        x: (xmin - margin(xmin, xmax), xmax + margin(xmin, xmax)),
+        y: (floor, ymax + margin(ymin, ymax)),
+        z: (zmin - margin(zmin, zmax), zmax + margin(zmin, zmax)),
+    };
+
+    // Sidecar metadata fills in whichever bounds it provides. Like
+    // `--fixed-bounds`, it speaks data coordinates, so which data axis
+    // lands on the plot's vertical depends on `--no-axis-swap`.
+    let bounds = match meta {
+        Some(m) if config.no_axis_swap => Bounds {
+            x: (m.xmin.unwrap_or(auto.x.0), m.xmax.unwrap_or(auto.x.1)),
+            y: (m.ymin.unwrap_or(auto.y.0), m.ymax.unwrap_or(auto.y.1)),
+            z: (m.zmin.unwrap_or(auto.z.0), m.zmax.unwrap_or(auto.z.1)),
+        },
+        Some(m) => Bounds {
+            x: (m.xmin.unwrap_or(auto.x.0), m.xmax.unwrap_or(auto.x.1)),
+            y: (m.zmin.unwrap_or(auto.y.0), m.zmax.unwrap_or(auto.y.1)),
+            z: (m.ymin.unwrap_or(auto.z.0), m.ymax.unwrap_or(auto.z.1)),
+        },
+        None => auto,
+    };
+    if config.equal_aspect {
+        equalize_aspect(bounds)
+    } else {
+        bounds
+    }
+}
+
+/// Expand the smaller axis ranges about their centers until all three
+/// spans match the largest, so one data unit has the same visual length on
+/// every axis (`--equal-aspect`).
+fn equalize_aspect(b: Bounds) -> Bounds {
+    let span = |r: (f64, f64)| r.1 - r.0;
+    let target = span(b.x).max(span(b.y)).max(span(b.z));
+    let widen = |r: (f64, f64)| {
+        let pad = (target - span(r)) / 2.0;
+        (r.0 - pad, r.1 + pad)
+    };
+    Bounds {
+        x: widen(b.x),
+        y: widen(b.y),
+        z: widen(b.z),
+    }
+}
+
+/// Leading-sample index for every output frame, honouring start/end/skip
+/// and `--bounce`.
+fn frame_indices(n: usize, config: &Config) -> Vec<usize> {
+    let end_frame = config.end_frame.unwrap_or(n).min(n);
+    let mut leads: Vec<usize> = (config.start..end_frame)
+        .step_by(config.skip.max(1))
+        .collect();
+    if config.bounce {
+        let mut back = leads.clone();
+        back.reverse();
+        leads.extend(back.into_iter().skip(1));
+    }
+    if config.reverse {
+        leads.reverse();
+    }
+    leads
+}
+
+/// [`frame_indices`] with `--filter-expr` applied: frames whose leading
+/// sample fails the predicate are dropped from the animation.
+fn filtered_leads(scene: &Scene) -> Result<Vec<usize>, TrajViewerError> {
+    let config = scene.config;
+    let mut leads = frame_indices(scene.xyz.len(), config);
+    let Some(spec) = &config.filter_expr else {
+        return Ok(leads);
+    };
+
+    let (column, op, value) = parse_filter_expr(spec)?;
+    leads.retain(|&lead| {
+        let p = scene.xyz[lead];
+        let v = match column.as_str() {
+            "x" => p.0,
+            // Plot space is (x, z, y) by default; report data coordinates.
+            "y" if config.no_axis_swap => p.1,
+            "y" => p.2,
+            "z" if config.no_axis_swap => p.2,
+            "z" => p.1,
+            "t" => scene.ts[lead],
+            "speed" => scene.speeds[lead],
+            _ => scene.accels[lead],
+        };
+        match op.as_str() {
+            "<" => v < value,
+            "<=" => v <= value,
+            ">" => v > value,
+            ">=" => v >= value,
+            "==" => v == value,
+            _ => v != value,
+        }
+    });
+    if leads.is_empty() {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--filter-expr `{spec}` matches no frames"
+        )));
+    }
+    Ok(leads)
+}
+
+/// Parse `--filter-expr` into `(column, operator, value)`.
+fn parse_filter_expr(spec: &str) -> Result<(String, String, f64), TrajViewerError> {
+    let invalid = || {
+        TrajViewerError::InvalidConfig(format!(
+            "--filter-expr expects `column op value` (e.g. `speed > 5`), got `{spec}`"
+        ))
+    };
+    let parts: Vec<&str> = spec.split_whitespace().collect();
+    let [column, op, value] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if !["x", "y", "z", "t", "speed", "accel"].contains(column) {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--filter-expr column must be one of x, y, z, t, speed, accel; got `{column}`"
+        )));
+    }
+    if !["<", "<=", ">", ">=", "==", "!="].contains(op) {
+        return Err(invalid());
+    }
+    let value: f64 = value.parse().map_err(|_| invalid())?;
+    Ok((column.to_string(), op.to_string(), value))
+}
+
+/// Tracks rendering throughput and, under `--verbose`, periodically prints
+/// the instantaneous frame rate and an ETA for the remaining frames.
+struct ThroughputLog {
+    enabled: bool,
+    last: Instant,
+    frames_at_last: usize,
+}
+
+/// How many frames go by between two throughput reports.
+const THROUGHPUT_INTERVAL: usize = 10;
+
+impl ThroughputLog {
+    fn new(enabled: bool) -> ThroughputLog {
+        ThroughputLog {
+            enabled,
+            last: Instant::now(),
+            frames_at_last: 0,
+        }
+    }
+
+    fn tick(&mut self, frames_written: usize, total_frames: usize) {
+        if !self.enabled || !frames_written.is_multiple_of(THROUGHPUT_INTERVAL) {
+            return;
+        }
+        let elapsed = self.last.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        let fps = (frames_written - self.frames_at_last) as f64 / elapsed;
+        let remaining = total_frames.saturating_sub(frames_written);
+        println!(
+            "frame {frames_written}/{total_frames}: {fps:.1} frames/s, ETA {:.1}s",
+            remaining as f64 / fps.max(f64::EPSILON)
+        );
+        self.last = Instant::now();
+        self.frames_at_last = frames_written;
+    }
+}
+
+fn render_gif(scene: &Scene, started: Instant) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let leads = filtered_leads(scene)?;
+    let output_path = output_path_for(
+        config,
+        &format!("{}_traj.gif", config.filekey),
+        "gif",
+        leads.len(),
+    )?;
+    let delay_ms = u32::from(validated_delay_cs(config)) * 10;
+
+    let root = BitMapBackend::gif(&output_path, (config.width, config.height), delay_ms)
+        .map_err(draw_err)?
+        .into_drawing_area();
+
+    // The bar counts rendered frames, not sample indices: a total of
+    // `end_frame` with `skip`-sized increments finishes early (or never)
+    // whenever `end_frame` is not a clean multiple of `skip`.
+    let mut progress = Progress::new();
+    let bar = progress.bar(leads.len(), "Rendering");
+    let mut throughput = ThroughputLog::new(config.verbose);
+
+    let background = if config.cache_background {
+        if static_camera(scene).is_some() {
+            Some(render_background(scene)?)
+        } else {
+            eprintln!(
+                "warning: --cache-background needs constant --camera-keyframes; ignoring"
+            );
+            None
+        }
+    } else {
+        None
+    };
+
+    let chapters = parse_chapters(&config.chapter_markers)?;
+
+    let mut frames_written = 0;
+    for (frame_no, &lead) in leads.iter().enumerate() {
+        if cancelled() {
+            break;
+        }
+        // `--chapter-markers`: a labeled black separator held for a few
+        // frames before the chapter's first real frame.
+        if let Some((_, name)) = chapters.iter().find(|(f, _)| *f == frame_no) {
+            for _ in 0..CHAPTER_HOLD_FRAMES {
+                draw_chapter_frame(&root, name)?;
+                root.present().map_err(draw_err)?;
+                frames_written += 1;
+            }
+        }
+        draw_frame_over(&root, scene, lead, frame_no, background.as_deref())?;
+        root.present().map_err(draw_err)?;
+        frames_written += 1;
+        progress.inc_and_draw(&bar, 1);
+        throughput.tick(frames_written, leads.len());
+    }
+    // The drawing buffer still holds the final frame; present it again to
+    // hold the endpoint before the loop restarts. Skipped on cancellation,
+    // where the last written frame is not the real endpoint.
+    if !cancelled() {
+        for _ in 0..config.hold_last {
+            root.present().map_err(draw_err)?;
+            frames_written += 1;
+        }
+    }
+    drop(root);
+
+    Ok(RenderReport {
+        frames_written,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// GIF rendering through the internal encoder, honouring `--gif-colors`
+/// (palette quantization) and `--gif-scale` (post-render downscale).
+///
+/// Frames are rendered at full resolution, box-filtered down and palette
+/// quantized with NeuQuant before encoding.
+fn render_gif_quantized(
+    scene: &Scene,
+    started: Instant,
+    mut hook: Option<&mut FrameHook>,
+) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let colors = config.gif_colors.unwrap_or(256);
+    if !(2..=256).contains(&colors) {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--gif-colors must be between 2 and 256, got {colors}"
+        )));
+    }
+    let scale = config.gif_scale.unwrap_or(1.0);
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--gif-scale must be in (0, 1], got {scale}"
+        )));
+    }
+
+    let (w, h) = (config.width, config.height);
+    let (out_w, out_h) = (
+        ((w as f64 * scale) as u32).max(1),
+        ((h as f64 * scale) as u32).max(1),
+    );
+    let leads = filtered_leads(scene)?;
+    let output_path = output_path_for(
+        config,
+        &format!("{}_traj.gif", config.filekey),
+        "gif",
+        leads.len(),
+    )?;
+
+    let file = std::fs::File::create(&output_path)?;
+    let mut encoder = gif::Encoder::new(file, out_w as u16, out_h as u16, &[])
+        .map_err(|e| TrajViewerError::Drawing(e.to_string()))?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|e| TrajViewerError::Drawing(e.to_string()))?;
+    let delay_cs = validated_delay_cs(config);
+
+    // `--delay-from-time`: each frame holds for the data-time gap to the
+    // next frame (scaled by `--time-scale`), so irregular sampling plays
+    // back at its real pace. The last frame reuses the constant delay.
+    let frame_delay_cs = |frame_no: usize| -> u16 {
+        if !config.delay_from_time {
+            return delay_cs;
+        }
+        let Some((&here, &next)) = leads.get(frame_no).zip(leads.get(frame_no + 1)) else {
+            return delay_cs;
+        };
+        let dt = (scene.ts[next] - scene.ts[here]).max(0.0);
+        (dt * config.time_scale * 100.0).round().clamp(1.0, u16::MAX as f64) as u16
+    };
+    let mut progress = Progress::new();
+    let bar = progress.bar(leads.len(), "Rendering");
+    let mut throughput = ThroughputLog::new(config.verbose);
+
+    let chapters = parse_chapters(&config.chapter_markers)?;
+
+    let mut buffer = vec![0u8; (w * h * 3) as usize];
+    let mut frames_written = 0;
+    let mut last_frame: Option<gif::Frame> = None;
+    for (frame_no, &lead) in leads.iter().enumerate() {
+        if cancelled() {
+            break;
+        }
+        // `--chapter-markers`: a labeled black separator held for a few
+        // frames before the chapter's first real frame, mirroring the
+        // plotters-encoder path.
+        if let Some((_, name)) = chapters.iter().find(|(f, _)| *f == frame_no) {
+            {
+                let root = BitMapBackend::with_buffer(&mut buffer, (w, h)).into_drawing_area();
+                draw_chapter_frame(&root, name)?;
+                root.present().map_err(draw_err)?;
+            }
+            let img = image::RgbImage::from_raw(w, h, buffer.clone())
+                .ok_or_else(|| TrajViewerError::Drawing("frame buffer size mismatch".into()))?;
+            let frame =
+                write_quantized_frame(&mut encoder, img, (out_w, out_h), colors, delay_cs)?;
+            frames_written += 1;
+            for _ in 1..CHAPTER_HOLD_FRAMES {
+                encoder
+                    .write_frame(&frame)
+                    .map_err(|e| TrajViewerError::Drawing(e.to_string()))?;
+                frames_written += 1;
+            }
+        }
+        {
+            let root = BitMapBackend::with_buffer(&mut buffer, (w, h)).into_drawing_area();
+            draw_frame(&root, scene, lead, frame_no)?;
+            root.present().map_err(draw_err)?;
+        }
+
+        let mut img = image::RgbImage::from_raw(w, h, buffer.clone())
+            .ok_or_else(|| TrajViewerError::Drawing("frame buffer size mismatch".into()))?;
+        if let Some(hook) = &mut hook {
+            let (pitch, yaw, cam_scale) = if scene.keyframes.is_empty() {
+                (0.25, yaw_at(frame_no, config), config.view_scale)
+            } else {
+                camera_at(&scene.keyframes, frame_no)
+            };
+            hook(&mut img, &FrameContext {
+                frame_no,
+                t0: scene.ts[lead],
+                pitch,
+                yaw,
+                scale: cam_scale,
+            });
+        }
+        let frame = write_quantized_frame(
+            &mut encoder,
+            img,
+            (out_w, out_h),
+            colors,
+            frame_delay_cs(frame_no),
+        )?;
+        last_frame = Some(frame);
+
+        frames_written += 1;
+        progress.inc_and_draw(&bar, 1);
+        throughput.tick(frames_written, leads.len());
+    }
+
+    if let (Some(mut frame), false) = (last_frame, cancelled()) {
+        if let Some(ms) = config.hold_delay_ms {
+            // Same u16-centisecond field `validated_delay_cs` guards for
+            // `--secs`: clamp rather than wrap or silently hand the
+            // player its default speed.
+            let cs = ms / 10;
+            frame.delay = if cs < 1 {
+                eprintln!(
+                    "warning: --hold-delay-ms {ms} is below one centisecond, the GIF \
+                     minimum; using 10 ms"
+                );
+                1
+            } else if cs > u64::from(u16::MAX) {
+                eprintln!(
+                    "warning: --hold-delay-ms {ms} overflows the GIF delay field; \
+                     using 655350 ms"
+                );
+                u16::MAX
+            } else {
+                cs as u16
+            };
+        }
+        for _ in 0..config.hold_last {
+            encoder
+                .write_frame(&frame)
+                .map_err(|e| TrajViewerError::Drawing(e.to_string()))?;
+            frames_written += 1;
+        }
+    }
+    drop(encoder);
+
+    if let Ok(meta) = std::fs::metadata(&output_path) {
+        println!("wrote {} ({} bytes)", output_path.display(), meta.len());
+    }
+
+    Ok(RenderReport {
+        frames_written,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Resolve the filename of a single-file output: `--output-name` with
+/// its placeholders substituted, or the default name. Templates must
+/// stay within `--output-dir` and carry the extension the mode writes.
+fn output_path_for(
+    config: &Config,
+    default_name: &str,
+    extension: &str,
+    frames: usize,
+) -> Result<PathBuf, TrajViewerError> {
+    let dir = Path::new(&config.output_dir);
+    let Some(template) = &config.output_name else {
+        return Ok(dir.join(default_name));
+    };
+
+    let mode = match config.mode {
+        Mode::Gif => "gif",
+        Mode::Accumulate => "accumulate",
+        Mode::PngSequence => "png-sequence",
+        Mode::Heatmap => "heatmap",
+        Mode::Density3d => "density3d",
+    };
+    let name = template
+        .replace("{filekey}", &config.filekey)
+        .replace("{mode}", mode)
+        .replace("{frames}", &frames.to_string())
+        .replace("{date}", &utc_date());
+    if Path::new(&name).is_absolute() || name.split(['/', '\\']).any(|part| part == "..") {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--output-name `{template}` must resolve within --output-dir"
+        )));
+    }
+    let path = dir.join(&name);
+    if path.extension().and_then(|e| e.to_str()) != Some(extension) {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--output-name `{template}` needs a .{extension} extension for this mode"
+        )));
+    }
+    // The template may introduce subdirectories below the output dir.
+    ensure_parent_dir(&path)?;
+    Ok(path)
+}
+
+/// Today's UTC date as `YYYY-MM-DD`, for `{date}` in `--output-name`
+/// (civil-from-days; avoids pulling in a date crate for one string).
+fn utc_date() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// Parse a `--grid RxC` specification like `2x3`.
+fn parse_grid(spec: &str) -> Result<(usize, usize), TrajViewerError> {
+    let invalid = || {
+        TrajViewerError::InvalidConfig(format!(
+            "--grid expects RxC (e.g. 2x3), got `{spec}`"
+        ))
+    };
+    let (rows, cols) = spec.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let rows: usize = rows.trim().parse().map_err(|_| invalid())?;
+    let cols: usize = cols.trim().parse().map_err(|_| invalid())?;
+    if rows == 0 || cols == 0 {
+        return Err(invalid());
+    }
+    Ok((rows, cols))
+}
+
+/// Downscale, palette-quantize and write one full-resolution RGB frame to
+/// the GIF encoder, returning the encoded frame so callers can rewrite it
+/// cheaply for holds.
+fn write_quantized_frame(
+    encoder: &mut gif::Encoder<std::fs::File>,
+    img: image::RgbImage,
+    (out_w, out_h): (u32, u32),
+    colors: usize,
+    delay: u16,
+) -> Result<gif::Frame<'static>, TrajViewerError> {
+    let img = if (out_w, out_h) != img.dimensions() {
+        image::imageops::thumbnail(&img, out_w, out_h)
+    } else {
+        img
+    };
+    let rgba: Vec<u8> = img
+        .pixels()
+        .flat_map(|p| [p.0[0], p.0[1], p.0[2], 255])
+        .collect();
+    let quant = color_quant::NeuQuant::new(10, colors, &rgba);
+    let indices: Vec<u8> = rgba.chunks(4).map(|px| quant.index_of(px) as u8).collect();
+
+    let mut frame = gif::Frame {
+        width: out_w as u16,
+        height: out_h as u16,
+        delay,
+        ..gif::Frame::default()
+    };
+    frame.palette = Some(quant.color_map_rgb());
+    frame.buffer = indices.into();
+    encoder
+        .write_frame(&frame)
+        .map_err(|e| TrajViewerError::Drawing(e.to_string()))?;
+    Ok(frame)
+}
+
+/// How many GIF frames each `--chapter-markers` separator is held for.
+const CHAPTER_HOLD_FRAMES: usize = 8;
+
+/// Parse `--chapter-markers` `FRAME:NAME` entries.
+fn parse_chapters(specs: &[String]) -> Result<Vec<(usize, String)>, TrajViewerError> {
+    specs
+        .iter()
+        .map(|spec| {
+            let invalid = || {
+                TrajViewerError::InvalidConfig(format!(
+                    "--chapter-markers expects FRAME:NAME entries (e.g. 0:baseline), got `{spec}`"
+                ))
+            };
+            let (frame, name) = spec.split_once(':').ok_or_else(invalid)?;
+            let frame = frame.trim().parse().map_err(|_| invalid())?;
+            Ok((frame, name.trim().to_string()))
+        })
+        .collect()
+}
+
+/// A chapter separator: a black frame with the chapter name centered,
+/// placed by the same average-glyph-width estimate as the bottom caption.
+fn draw_chapter_frame(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    name: &str,
+) -> Result<(), TrajViewerError> {
+    root.fill(&BLACK).map_err(draw_err)?;
+    let (w, h) = root.dim_in_pixel();
+    let x = (w as i32 - name.len() as i32 * 12) / 2;
+    root.draw(&Text::new(
+        name.to_string(),
+        (x.max(0), h as i32 / 2 - 12),
+        ("sans-serif", 24).into_font().color(&WHITE),
+    ))
+    .map_err(draw_err)
+}
+
+/// Render every trajectory in its own cell of an RxC grid, synchronized by
+/// frame. Cells whose trajectory is shorter freeze on their last sample.
+fn render_grid(
+    cells: &[TrajData],
+    rows: usize,
+    cols: usize,
+    config: &Config,
+    started: Instant,
+) -> Result<RenderReport, TrajViewerError> {
+    if cells.len() > rows * cols {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--grid {rows}x{cols} has {} cells but {} trajectories were given",
+            rows * cols,
+            cells.len()
+        )));
+    }
+
+    let output_path = Path::new(&config.output_dir).join(format!("{}_grid.gif", config.filekey));
+    let delay_ms = u32::from(validated_delay_cs(config)) * 10;
+    let root = BitMapBackend::gif(&output_path, (config.width, config.height), delay_ms)
+        .map_err(draw_err)?
+        .into_drawing_area();
+
+    let scenes: Vec<Scene> = cells
+        .iter()
+        .map(|cell| build_scene(cell, &[], config))
+        .collect::<Result<_, _>>()?;
+    let max_n = cells.iter().map(|c| c.xyz.len()).max().unwrap_or(0);
+    let leads = frame_indices(max_n, config);
+
+    let mut progress = Progress::new();
+    let bar = progress.bar(leads.len(), "Rendering grid");
+    let mut throughput = ThroughputLog::new(config.verbose);
+
+    let theme = theme_colors(config);
+    let mut frames_written = 0;
+    for (frame_no, &lead) in leads.iter().enumerate() {
+        if cancelled() {
+            break;
+        }
+        root.fill(&theme.background).map_err(draw_err)?;
+        let areas = root.split_evenly((rows, cols));
+        for (scene, area) in scenes.iter().zip(areas.iter()) {
+            let cell_lead = lead.min(scene.xyz.len().saturating_sub(1));
+            draw_frame(area, scene, cell_lead, frame_no)?;
+        }
+        root.present().map_err(draw_err)?;
+        frames_written += 1;
+        progress.inc_and_draw(&bar, 1);
+        throughput.tick(frames_written, leads.len());
+    }
+    drop(root);
+
+    Ok(RenderReport {
+        frames_written,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Tile RxC evenly-spaced frames of the animation into one PNG
+/// (`--contact-sheet`), each cell captioned with its frame number and
+/// time, so a long trajectory can be scanned without playing the GIF.
+fn render_contact_sheet(
+    scene: &Scene,
+    spec: &str,
+    started: Instant,
+) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let (rows, cols) = parse_grid(spec)?;
+    let tiles = rows * cols;
+    let leads = frame_indices(scene.xyz.len(), config);
+    if leads.is_empty() {
+        return Err(TrajViewerError::InvalidConfig(
+            "--contact-sheet has no frames to sample; check --start/--end-frame".into(),
+        ));
+    }
+
+    let output_path =
+        Path::new(&config.output_dir).join(format!("{}_contact.png", config.filekey));
+    let root = BitMapBackend::new(&output_path, (config.width, config.height)).into_drawing_area();
+    root.fill(&theme_colors(config).background).map_err(draw_err)?;
+
+    let areas = root.split_evenly((rows, cols));
+    for (i, area) in areas.iter().enumerate() {
+        // Evenly spaced across the animation, keeping both endpoints.
+        let frame_no = if tiles == 1 {
+            0
+        } else {
+            i * (leads.len() - 1) / (tiles - 1)
+        };
+        let lead = leads[frame_no];
+        draw_frame(area, scene, lead, frame_no)?;
+        let t = scene.ts.get(lead).copied().unwrap_or(0.0);
+        draw_text(area, &format!("frame {frame_no}  t={t:.2}"), (6, 6), 12, config)?;
+    }
+
+    root.present().map_err(draw_err)?;
+    drop(areas);
+    drop(root);
+    Ok(RenderReport {
+        frames_written: tiles,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+fn render_png_sequence(scene: &Scene, started: Instant) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let leads = filtered_leads(scene)?;
+    let mut progress = Progress::new();
+    let bar = progress.bar(leads.len(), "Rendering");
+    let mut throughput = ThroughputLog::new(config.verbose);
+
+    let mut frames_written = 0;
+    for (frame_no, &lead) in leads.iter().enumerate() {
+        if cancelled() {
+            break;
+        }
+        let filename = if config.name_by_time {
+            let t0 = scene.ts.get(lead).copied().unwrap_or(0.0);
+            format!("{}_t{:09.3}.png", config.filekey, t0)
+        } else {
+            format!("{}_{:05}.png", config.filekey, frame_no)
+        };
+        let path = Path::new(&config.output_dir).join(filename);
+        if config.embed_metadata {
+            draw_png_with_metadata(&path, scene, lead, frame_no)?;
+        } else {
+            let root =
+                BitMapBackend::new(&path, (config.width, config.height)).into_drawing_area();
+            draw_frame(&root, scene, lead, frame_no)?;
+            root.present().map_err(draw_err)?;
+        }
+        frames_written += 1;
+        progress.inc_and_draw(&bar, 1);
+        throughput.tick(frames_written, leads.len());
+    }
+
+    Ok(RenderReport {
+        frames_written,
+        output_path: PathBuf::from(&config.output_dir),
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Render the single frame whose trail window reaches `--goto-time` as
+/// `{filekey}_goto.png`. The frame index is looked up from `t`, so the
+/// camera yaw matches what the full animation shows at that moment.
+fn render_goto_time(
+    scene: &Scene,
+    t: f64,
+    started: Instant,
+) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let (t0, t1) = (
+        scene.ts.first().copied().unwrap_or(0.0),
+        scene.ts.last().copied().unwrap_or(0.0),
+    );
+    if t < t0 || t > t1 {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--goto-time {t} is outside the recording ({t0:.2} to {t1:.2})"
+        )));
+    }
+
+    let leads = frame_indices(scene.xyz.len(), config);
+    let frame_no = leads
+        .iter()
+        .position(|&lead| scene.ts[lead] >= t)
+        .unwrap_or(leads.len().saturating_sub(1));
+    let lead = leads.get(frame_no).copied().unwrap_or(0);
+
+    let output_path = Path::new(&config.output_dir).join(format!("{}_goto.png", config.filekey));
+    let root = BitMapBackend::new(&output_path, (config.width, config.height)).into_drawing_area();
+    draw_frame(&root, scene, lead, frame_no)?;
+    root.present().map_err(draw_err)?;
+    drop(root);
+
+    println!(
+        "t={t}: frame {frame_no} (lead sample {lead}, t={:.3})",
+        scene.ts[lead]
+    );
+    Ok(RenderReport {
+        frames_written: 1,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+fn render_preview(scene: &Scene, started: Instant) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let output_path = Path::new(&config.output_dir).join(format!("{}_preview.png", config.filekey));
+    let leads = frame_indices(scene.xyz.len(), config);
+    let mid = leads.len() / 2;
+    let lead = leads.get(mid).copied().unwrap_or(0);
+
+    if config.embed_metadata {
+        draw_png_with_metadata(&output_path, scene, lead, mid)?;
+    } else {
+        let root =
+            BitMapBackend::new(&output_path, (config.width, config.height)).into_drawing_area();
+        draw_frame(&root, scene, lead, mid)?;
+        root.present().map_err(draw_err)?;
+        drop(root);
+    }
+
+    Ok(RenderReport {
+        frames_written: 1,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Render one frame into a buffer and write it as a PNG with provenance
+/// tEXt chunks (`--embed-metadata`): filekey, frame index, the leading
+/// sample's timestamp, the source path and the tool version, so a frame
+/// can be traced back to its data without external bookkeeping.
+fn draw_png_with_metadata(
+    path: &Path,
+    scene: &Scene,
+    lead: usize,
+    frame_no: usize,
+) -> Result<(), TrajViewerError> {
+    let config = scene.config;
+    let png_err = |e: png::EncodingError| TrajViewerError::Drawing(e.to_string());
+
+    let mut buffer = vec![0u8; (config.width * config.height * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, (config.width, config.height))
+            .into_drawing_area();
+        draw_frame(&root, scene, lead, frame_no)?;
+        root.present().map_err(draw_err)?;
+    }
+
+    let source = match &config.sqlite {
+        Some(db) => db.display().to_string(),
+        None => format!("{}/{}", config.input_dir, config.filekey),
+    };
+    let t0 = scene.ts.get(lead).copied().unwrap_or(0.0);
+
+    let file = std::fs::File::create(path)?;
+    let mut encoder =
+        png::Encoder::new(std::io::BufWriter::new(file), config.width, config.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in [
+        ("traj_viewer:filekey", config.filekey.clone()),
+        ("traj_viewer:frame", frame_no.to_string()),
+        ("traj_viewer:t0", t0.to_string()),
+        ("traj_viewer:source", source),
+        ("traj_viewer:version", env!("CARGO_PKG_VERSION").to_string()),
+    ] {
+        encoder.add_text_chunk(keyword.into(), text).map_err(png_err)?;
+    }
+    let mut writer = encoder.write_header().map_err(png_err)?;
+    writer.write_image_data(&buffer).map_err(png_err)?;
+    Ok(())
+}
+
+/// Render the newest sample of `df` as `{output_dir}/{filekey}_live.png`,
+/// used by `--watch` to keep a live view of a growing file.
+pub fn render_live_frame(df: &DataFrame, config: &Config) -> Result<PathBuf, TrajViewerError> {
+    let main = TrajData::new(config.filekey.clone(), df, config)?;
+    let scene = build_scene(&main, &[], config)?;
+    let output_path = Path::new(&config.output_dir).join(format!("{}_live.png", config.filekey));
+
+    let lead = scene.xyz.len().saturating_sub(1);
+    let root = BitMapBackend::new(&output_path, (config.width, config.height)).into_drawing_area();
+    draw_frame(&root, &scene, lead, lead / config.skip.max(1))?;
+    root.present().map_err(draw_err)?;
+    drop(root);
+    Ok(output_path)
+}
+
+/// Draw one frame of the animation: the 3D box, the trail, projections,
+/// overlays and annotations.
+fn draw_frame(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    scene: &Scene,
+    lead: usize,
+    frame_no: usize,
+) -> Result<(), TrajViewerError> {
+    draw_frame_over(root, scene, lead, frame_no, None)
+}
+
+/// Camera parameters when every frame shares them (a constant keyframe
+/// path); `None` when the camera moves, e.g. the default yaw oscillation.
+fn static_camera(scene: &Scene) -> Option<(f64, f64, f64)> {
+    let first = scene.keyframes.first()?;
+    let params = (first.pitch, first.yaw, first.scale);
+    scene
+        .keyframes
+        .iter()
+        .all(|kf| (kf.pitch, kf.yaw, kf.scale) == params)
+        .then_some(params)
+}
+
+/// Render the static part of a frame (white fill plus axes) once, for
+/// `--cache-background` to reuse as the base of every frame.
+fn render_background(scene: &Scene) -> Result<Vec<u8>, TrajViewerError> {
+    let config = scene.config;
+    let mut buf = vec![0u8; (config.width * config.height * 3) as usize];
+    let theme = theme_colors(config);
+    {
+        let root =
+            BitMapBackend::with_buffer(&mut buf, (config.width, config.height)).into_drawing_area();
+        root.fill(&theme.background).map_err(draw_err)?;
+        let (chart_area, _) = split_projection_panels(&root, config);
+        let chart_area = if config.letterbox {
+            letterbox_area(chart_area, scene)
+        } else {
+            chart_area
+        };
+
+        let text_color = match &config.text_color {
+            Some(spec) => parse_color(spec)?,
+            None => theme.foreground,
+        };
+        let mut builder = ChartBuilder::on(&chart_area);
+        if config.caption_pos == CaptionPos::Top {
+            builder.caption(scene.title, ("sans-serif", 30).into_font().color(&text_color));
+        }
+        let mut chart = builder
+            .build_cartesian_3d(
+                scene.bounds.x.0..scene.bounds.x.1,
+                scene.bounds.y.0..scene.bounds.y.1,
+                scene.bounds.z.0..scene.bounds.z.1,
+            )
+            .map_err(draw_err)?;
+        if config.caption_pos == CaptionPos::Bottom {
+            draw_bottom_caption(&chart_area, scene.title, config)?;
+        }
+        let (pitch, yaw, scale) =
+            static_camera(scene).unwrap_or((0.25, yaw_at(0, config), config.view_scale));
+        chart.with_projection(|mut pb| {
+            pb.yaw = yaw;
+            pb.pitch = pitch;
+            pb.scale = scale;
+            pb.into_matrix()
+        });
+        draw_axes(&mut chart, text_color, config)?;
+        if let Some(spacing) = config.floor_grid {
+            draw_floor_grid(&mut chart, scene, spacing)?;
+        }
+        root.present().map_err(draw_err)?;
+    }
+    Ok(buf)
+}
+
+/// `--letterbox`: shrink `area` to a centered sub-area whose aspect
+/// ratio matches the data's horizontal-to-vertical extent, leaving the
+/// margins to the background fill so the box is never stretched.
+fn letterbox_area<'a>(
+    area: DrawingArea<BitMapBackend<'a>, Shift>,
+    scene: &Scene,
+) -> DrawingArea<BitMapBackend<'a>, Shift> {
+    let (w, h) = area.dim_in_pixel();
+    let x_span = (scene.bounds.x.1 - scene.bounds.x.0).abs();
+    let y_span = (scene.bounds.y.1 - scene.bounds.y.0).abs();
+    if w == 0 || h == 0 || x_span <= 0.0 || y_span <= 0.0 {
+        return area;
+    }
+
+    let target = x_span / y_span;
+    let canvas = w as f64 / h as f64;
+    if canvas > target {
+        // Canvas too wide: pillarbox left and right.
+        let margin = ((w as f64 - h as f64 * target) / 2.0) as u32;
+        area.margin(0, 0, margin, margin)
+    } else {
+        // Canvas too tall: letterbox top and bottom.
+        let margin = ((h as f64 - w as f64 / target) / 2.0) as u32;
+        area.margin(margin, margin, 0, 0)
+    }
+}
+
+/// Split off the lower projection-panel strip in the panels layout.
+fn split_projection_panels<'a>(
+    root: &DrawingArea<BitMapBackend<'a>, Shift>,
+    config: &Config,
+) -> (
+    DrawingArea<BitMapBackend<'a>, Shift>,
+    Option<DrawingArea<BitMapBackend<'a>, Shift>>,
+) {
+    match config.projection_layout {
+        ProjectionLayout::Panels => {
+            let split_at = (root.dim_in_pixel().1 as f64 * 0.7) as u32;
+            let (top, bottom) = root.split_vertically(split_at);
+            (top, Some(bottom))
+        }
+        ProjectionLayout::OnBox => (root.clone(), None),
+    }
+}
+
+fn draw_frame_over(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    scene: &Scene,
+    lead: usize,
+    frame_no: usize,
+    background: Option<&[u8]>,
+) -> Result<(), TrajViewerError> {
+    let config = scene.config;
+    let theme = theme_colors(config);
+    match background {
+        // Blit the cached fill-plus-axes bitmap instead of redrawing it.
+        Some(buf) => {
+            let (w, h) = root.dim_in_pixel();
+            let element = BitMapElement::with_owned_buffer((0, 0), (w, h), buf.to_vec())
+                .ok_or_else(|| {
+                    TrajViewerError::Drawing("background buffer size mismatch".into())
+                })?;
+            root.draw(&element).map_err(draw_err)?;
+        }
+        None => root.fill(&theme.background).map_err(draw_err)?,
+    }
+
+    // In the panels layout the lower strip holds the 2D projections.
+    let (chart_area, panel_row) = split_projection_panels(root, config);
+    let chart_area = if config.letterbox {
+        letterbox_area(chart_area, scene)
+    } else {
+        chart_area
+    };
+
+    let text_color = match &config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme.foreground,
+    };
+    let mut builder = ChartBuilder::on(&chart_area);
+    if config.caption_pos == CaptionPos::Top {
+        builder.caption(scene.title, ("sans-serif", 30).into_font().color(&text_color));
+    }
+    let mut chart = builder
+        .build_cartesian_3d(
+            scene.bounds.x.0..scene.bounds.x.1,
+            scene.bounds.y.0..scene.bounds.y.1,
+            scene.bounds.z.0..scene.bounds.z.1,
+        )
+        .map_err(draw_err)?;
+    if config.caption_pos == CaptionPos::Bottom {
+        draw_bottom_caption(&chart_area, scene.title, config)?;
+    }
+
+    let (pitch, yaw, scale) = if scene.keyframes.is_empty() {
+        (0.25, yaw_at(frame_no, config), config.view_scale)
+    } else {
+        camera_at(&scene.keyframes, frame_no)
+    };
+    let mut matrix: Option<ProjectionMatrix> = None;
+    chart.with_projection(|mut pb| {
+        pb.yaw = yaw;
+        pb.pitch = pitch;
+        pb.scale = scale;
+        let m = pb.into_matrix();
+        matrix = Some(m);
+        m
+    });
+    if let (Some(log), Some(m)) = (&scene.camera_log, matrix) {
+        if let Ok(mut log) = log.lock() {
+            log.push(CameraSample {
+                frame_no,
+                pitch,
+                yaw,
+                scale,
+                matrix: *m.as_ref(),
+            });
+        }
+    }
+
+    // The cached background already contains the axes and floor grid.
+    if background.is_none() {
+        draw_axes(&mut chart, text_color, config)?;
+        if let Some(spacing) = config.floor_grid {
+            draw_floor_grid(&mut chart, scene, spacing)?;
+        }
+    }
+
+    let trail_len = trail_length(scene, lead);
+    let from = lead.saturating_sub(trail_len);
+    let trail = &scene.xyz[from..=lead.min(scene.xyz.len().saturating_sub(1))];
+
+    // The polyline actually drawn: every sample, or a strided subset
+    // (keeping the endpoints) when `--trail-stride` asks for fewer
+    // segments. Each entry keeps its absolute sample index for coloring.
+    let stride = config.trail_stride.max(1);
+    let drawn: Vec<(usize, Point3)> = trail
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| stride == 1 || i.is_multiple_of(stride) || *i == trail.len() - 1)
+        .map(|(i, p)| (from + i, *p))
+        .collect();
+
+    // `--comet`: segment opacity ramps from fully transparent at the
+    // oldest trail sample to fully opaque at the leading point.
+    let comet_alpha = |sample: usize| {
+        if !config.comet {
+            return 1.0;
+        }
+        let span = (lead - from).max(1) as f64;
+        ((sample.saturating_sub(from)) as f64 / span).clamp(0.0, 1.0)
+    };
+
+    // `--gap-threshold`: true when the segment between two sample indices
+    // spans a tracking dropout and must not be drawn.
+    let gap = |a: usize, b: usize| {
+        config
+            .gap_threshold
+            .is_some_and(|threshold| scene.ts[b] - scene.ts[a] > threshold)
+    };
+
+    // The body.
+    if config.hide_body {
+        // `--hide-body`: projections only; the trail slice above still
+        // feeds them.
+    } else if config.render_style == RenderStyle::Tube {
+        draw_tube(&mut chart, scene, &drawn)?;
+    } else if let Some(labels) = scene.labels {
+        for w in drawn.windows(2) {
+            if gap(w[0].0, w[1].0) {
+                continue;
+            }
+            let color = label_color(&scene.label_classes, labels[w[0].0].as_ref());
+            chart
+                .draw_series(LineSeries::new([w[0].1, w[1].1], color.mix(comet_alpha(w[0].0))))
+                .map_err(draw_err)?;
+        }
+        // Legend mapping colors to labels.
+        let (width, _) = root.dim_in_pixel();
+        for (i, class) in scene.label_classes.iter().enumerate() {
+            let y = 40 + 18 * i as i32;
+            root.draw(&Rectangle::new(
+                [(width as i32 - 120, y), (width as i32 - 108, y + 12)],
+                Palette99::pick(i).to_rgba().filled(),
+            ))
+            .map_err(draw_err)?;
+            draw_text(root, class, (width as i32 - 102, y), 13, config)?;
+        }
+    } else if config.color_by_active() {
+        for w in drawn.windows(2) {
+            if gap(w[0].0, w[1].0) {
+                continue;
+            }
+            let v = segment_scalar(scene, w[0].0).unwrap_or(0.0);
+            let color = scalar_color(v, config.colormap).mix(comet_alpha(w[0].0));
+            chart
+                .draw_series(LineSeries::new([w[0].1, w[1].1], color))
+                .map_err(draw_err)?;
+        }
+    } else if config.trail_color.is_some() || config.lead_color.is_some() {
+        // `--trail-color`/`--lead-color`: flat trail color with the last
+        // `--lead-length` segments in the contrasting lead color.
+        let trail_color = match &config.trail_color {
+            Some(spec) => parse_color(spec)?,
+            None => theme.foreground,
+        };
+        let lead_color = match &config.lead_color {
+            Some(spec) => parse_color(spec)?,
+            None => trail_color,
+        };
+        let segments = drawn.len().saturating_sub(1);
+        for (i, w) in drawn.windows(2).enumerate() {
+            if gap(w[0].0, w[1].0) {
+                continue;
+            }
+            let color = if i + config.lead_length >= segments {
+                lead_color
+            } else {
+                trail_color
+            };
+            chart
+                .draw_series(LineSeries::new(
+                    [w[0].1, w[1].1],
+                    color.mix(comet_alpha(w[0].0)),
+                ))
+                .map_err(draw_err)?;
+        }
+    } else if config.comet {
+        for w in drawn.windows(2) {
+            if gap(w[0].0, w[1].0) {
+                continue;
+            }
+            chart
+                .draw_series(LineSeries::new(
+                    [w[0].1, w[1].1],
+                    theme.foreground.mix(comet_alpha(w[0].0)),
+                ))
+                .map_err(draw_err)?;
+        }
+    } else {
+        for run in gap_runs(&drawn, &gap) {
+            chart
+                .draw_series(LineSeries::new(run.iter().map(|(_, p)| *p), &theme.foreground))
+                .map_err(draw_err)?;
+        }
+    }
+
+    // Per-sample markers showing the sampling density.
+    if !config.hide_body && config.point_every > 0 && config.point_size > 0 {
+        chart
+            .draw_series(
+                trail
+                    .iter()
+                    .step_by(config.point_every)
+                    .map(|p| Circle::new(*p, config.point_size, theme.foreground.filled())),
+            )
+            .map_err(draw_err)?;
+    }
+
+    // Wall projections of the trail.
+    for plane in &config.projection_planes() {
+        if panel_row.is_some() {
+            break;
+        }
+        let (base, alpha): (RGBColor, f64) = match plane.as_str() {
+            "xy" => (theme.xy, projection_alpha(config.xy_alpha, config)),
+            "xz" => (theme.xz, projection_alpha(config.xz_alpha, config)),
+            "yz" => (theme.yz, projection_alpha(config.yz_alpha, config)),
+            _ => continue,
+        };
+        let points: Vec<Point3> = drawn
+            .iter()
+            .filter_map(|(_, p)| wall_point(scene, plane, *p))
+            .collect();
+        if config.color_projections && config.color_by_active() {
+            // Same per-segment scheme as the body, faded so the walls stay
+            // visually secondary.
+            for (w, d) in points.windows(2).zip(drawn.windows(2)) {
+                if gap(d[0].0, d[1].0) {
+                    continue;
+                }
+                let v = segment_scalar(scene, d[0].0).unwrap_or(0.0);
+                chart
+                    .draw_series(LineSeries::new([w[0], w[1]], scalar_color(v, config.colormap).mix(alpha)))
+                    .map_err(draw_err)?;
+            }
+        } else {
+            for run in gap_runs(&drawn, &gap) {
+                let shadow = run.iter().filter_map(|(_, p)| wall_point(scene, plane, *p));
+                chart
+                    .draw_series(LineSeries::new(shadow, base.mix(alpha)))
+                    .map_err(draw_err)?;
+            }
+        }
+    }
+
+    // Overlaid trajectories, synchronized by sample index.
+    for (i, overlay) in scene.overlays.iter().enumerate() {
+        let olead = lead.min(overlay.xyz.len().saturating_sub(1));
+        let ofrom = olead.saturating_sub(trail_len);
+        let color = Palette99::pick(i + 1).to_rgba();
+        chart
+            .draw_series(LineSeries::new(
+                overlay.xyz[ofrom..=olead].iter().copied(),
+                color,
+            ))
+            .map_err(draw_err)?
+            .label(overlay.name.clone());
+    }
+
+    // `--skeleton`: connect the leading points of all segment trails,
+    // sketching the body posture in every frame.
+    if config.skeleton && !scene.overlays.is_empty() {
+        let last = lead.min(scene.xyz.len().saturating_sub(1));
+        let mut joints = vec![scene.xyz[last]];
+        for overlay in scene.overlays {
+            let olead = lead.min(overlay.xyz.len().saturating_sub(1));
+            joints.push(overlay.xyz[olead]);
+        }
+        chart
+            .draw_series(LineSeries::new(joints, theme.foreground.mix(0.7).stroke_width(2)))
+            .map_err(draw_err)?;
+    }
+
+    // Leading point marker.
+    if let Some(p) = trail.last().filter(|_| !config.hide_body) {
+        chart
+            .draw_series(std::iter::once(Circle::new(*p, 4, RED.filled())))
+            .map_err(draw_err)?;
+    }
+
+    // `--show-hull`: spatial-extent outline, persistent across frames.
+    if !scene.hull.is_empty() {
+        chart
+            .draw_series(LineSeries::new(
+                scene.hull.iter().copied(),
+                theme.foreground.mix(0.6).stroke_width(2),
+            ))
+            .map_err(draw_err)?;
+    }
+
+    // `--tie-lines`: anchor the leading point to its wall shadows, drawn
+    // only for the current sample so the connectors never accumulate.
+    if config.tie_lines && panel_row.is_none() {
+        if let Some(p) = trail.last() {
+            for plane in &config.projection_planes() {
+                let Some(q) = wall_point(scene, plane, *p) else {
+                    continue;
+                };
+                chart
+                    .draw_series(LineSeries::new([*p, q], theme.foreground.mix(config.tie_line_alpha)))
+                    .map_err(draw_err)?;
+            }
+        }
+    }
+
+    // Velocity arrow at the leading point, from the last two trail samples.
+    if config.show_velocity_arrow && trail.len() >= 2 && lead >= 1 {
+        let p = trail[trail.len() - 1];
+        let q = trail[trail.len() - 2];
+        let dt = (scene.ts[lead] - scene.ts[lead - 1]).max(f64::EPSILON);
+        let v = ((p.0 - q.0) / dt, (p.1 - q.1) / dt, (p.2 - q.2) / dt);
+        let s = config.arrow_scale;
+        let tip = (p.0 + v.0 * s, p.1 + v.1 * s, p.2 + v.2 * s);
+        for segment in arrow_segments(p, tip) {
+            chart
+                .draw_series(LineSeries::new(segment, RED.stroke_width(2)))
+                .map_err(draw_err)?;
+        }
+    }
+
+    // Event markers, persistent across frames; frames whose trail window
+    // contains the event get a highlight border.
+    let window_start = scene.ts.get(from).copied().unwrap_or(0.0);
+    let window_end = scene.ts.get(lead).copied().unwrap_or(0.0);
+    let mut window_has_event = false;
+    for &(t, p) in &scene.marks {
+        chart
+            .draw_series(std::iter::once(TriangleMarker::new(p, 7, BLUE.filled())))
+            .map_err(draw_err)?;
+        if t >= window_start && t <= window_end {
+            window_has_event = true;
+        }
+    }
+    if window_has_event {
+        let (w, h) = root.dim_in_pixel();
+        root.draw(&Rectangle::new(
+            [(1, 1), (w as i32 - 2, h as i32 - 2)],
+            RED.stroke_width(3),
+        ))
+        .map_err(draw_err)?;
+    }
+
+    // Annotations.
+    let t0 = scene.ts.get(lead).copied().unwrap_or(0.0);
+    draw_text(root, &format!("t = {t0:.2}"), (10, 40), 16, config)?;
+    if let Some(period) = scene.period {
+        draw_text(root, &format!("period = {period:.2}"), (10, 60), 16, config)?;
+    }
+    if config.show_window {
+        let t_from = scene.ts.get(from).copied().unwrap_or(0.0);
+        let y = if scene.period.is_some() { 80 } else { 60 };
+        draw_text(
+            root,
+            &format!("samples {from}-{lead}, t {t_from:.2}-{t0:.2}"),
+            (10, y),
+            14,
+            config,
+        )?;
+    }
+    if let Some((turning, straightness)) = scene.tortuosity {
+        let (_, h) = root.dim_in_pixel();
+        draw_text(
+            root,
+            &format!("turning {turning:.1} rad, straightness {straightness:.3}"),
+            (10, h as i32 - 40),
+            14,
+            config,
+        )?;
+    }
+    if let Some(units) = &scene.units {
+        let (_, h) = root.dim_in_pixel();
+        draw_text(root, &format!("units: {units}"), (10, h as i32 - 20), 14, config)?;
+    }
+
+    if config.color_by_active() {
+        draw_colorbar(root, scene)?;
+    }
+
+    if let Some(panel_row) = panel_row {
+        draw_projection_panels(&panel_row, scene, trail)?;
+    }
+
+    Ok(())
+}
+
+/// Draw the selected projections as small 2D charts in a horizontal strip.
+fn draw_projection_panels(
+    row: &DrawingArea<BitMapBackend, Shift>,
+    scene: &Scene,
+    trail: &[Point3],
+) -> Result<(), TrajViewerError> {
+    let planes: Vec<String> = scene
+        .config
+        .projection_planes()
+        .into_iter()
+        .filter(|p| matches!(p.as_str(), "xy" | "xz" | "yz"))
+        .collect();
+    if planes.is_empty() {
+        return Ok(());
+    }
+
+    let theme = theme_colors(scene.config);
+    let text_color = match &scene.config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme.foreground,
+    };
+    let areas = row.split_evenly((1, planes.len()));
+    for (plane, area) in planes.iter().zip(areas.iter()) {
+        let Some(((ix, iy), x_range, y_range)) = plane_axes(scene, plane) else {
+            continue;
+        };
+        let points: Vec<(f64, f64)> =
+            trail.iter().map(|p| (get_axis(p, ix), get_axis(p, iy))).collect();
+        let trail_color = match plane.as_str() {
+            "xz" => theme.xz,
+            "yz" => theme.yz,
+            _ => theme.xy,
+        };
+
+        let mut panel = ChartBuilder::on(area)
+            .caption(
+                plane.to_uppercase(),
+                ("sans-serif", 14).into_font().color(&text_color),
+            )
+            .margin(5)
+            .x_label_area_size(15)
+            .y_label_area_size(20)
+            .build_cartesian_2d(x_range.0..x_range.1, y_range.0..y_range.1)
+            .map_err(draw_err)?;
+        panel
+            .configure_mesh()
+            .x_labels(3)
+            .y_labels(3)
+            .disable_mesh()
+            .label_style(("sans-serif", 10).into_font().color(&text_color))
+            .axis_style(text_color)
+            .draw()
+            .map_err(draw_err)?;
+        panel
+            .draw_series(LineSeries::new(points, trail_color.mix(0.8)))
+            .map_err(draw_err)?;
+    }
+    Ok(())
+}
+
+/// The plot-space component pair and data-coordinate axis ranges for a
+/// named projection plane, honouring `--no-axis-swap`; `None` for an
+/// unknown plane name.
+fn plane_axes(scene: &Scene, plane: &str) -> Option<((usize, usize), Range, Range)> {
+    let swap = !scene.config.no_axis_swap;
+    let (ix, iy) = match (plane, swap) {
+        ("xy", true) => (0, 2),
+        ("xy", false) => (0, 1),
+        ("xz", true) => (0, 1),
+        ("xz", false) => (0, 2),
+        ("yz", true) => (2, 1),
+        ("yz", false) => (1, 2),
+        _ => return None,
+    };
+    let bounds = |i: usize| match i {
+        0 => scene.bounds.x,
+        1 => scene.bounds.y,
+        _ => scene.bounds.z,
+    };
+    Some(((ix, iy), bounds(ix), bounds(iy)))
+}
+
+/// Render each selected projection plane as its own 2D animated GIF
+/// (`{filekey}_xy.gif`, `_xz.gif`, `_yz.gif`) for `--export-projections`,
+/// so each plane can be studied without the clutter of the combined view.
+fn export_projections(scene: &Scene) -> Result<(), TrajViewerError> {
+    let config = scene.config;
+    let delay_ms = u32::from(validated_delay_cs(config)) * 10;
+    // Frame-for-frame with the main animation, including `--filter-expr`.
+    let leads = filtered_leads(scene)?;
+    let theme = theme_colors(config);
+    let text_color = match &config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme.foreground,
+    };
+
+    for plane in &config.projection_planes() {
+        if cancelled() {
+            break;
+        }
+        let Some(((ix, iy), x_range, y_range)) = plane_axes(scene, plane) else {
+            continue;
+        };
+        let trail_color = match plane.as_str() {
+            "xz" => theme.xz,
+            "yz" => theme.yz,
+            _ => theme.xy,
+        };
+        let output_path =
+            Path::new(&config.output_dir).join(format!("{}_{plane}.gif", config.filekey));
+        let root = BitMapBackend::gif(&output_path, (config.width, config.height), delay_ms)
+            .map_err(draw_err)?
+            .into_drawing_area();
+
+        for &lead in &leads {
+            if cancelled() {
+                break;
+            }
+            root.fill(&theme.background).map_err(draw_err)?;
+            let mut chart = ChartBuilder::on(&root)
+                .caption(
+                    plane.to_uppercase(),
+                    ("sans-serif", 30).into_font().color(&text_color),
+                )
+                .margin(10)
+                .x_label_area_size(40)
+                .y_label_area_size(40)
+                .build_cartesian_2d(x_range.0..x_range.1, y_range.0..y_range.1)
+                .map_err(draw_err)?;
+            chart
+                .configure_mesh()
+                .label_style(("sans-serif", 12).into_font().color(&text_color))
+                .axis_style(text_color)
+                .draw()
+                .map_err(draw_err)?;
+
+            let from = lead.saturating_sub(trail_length(scene, lead));
+            let trail = &scene.xyz[from..=lead.min(scene.xyz.len().saturating_sub(1))];
+            chart
+                .draw_series(LineSeries::new(
+                    trail.iter().map(|p| (get_axis(p, ix), get_axis(p, iy))),
+                    &trail_color,
+                ))
+                .map_err(draw_err)?;
+            if let Some(p) = trail.last() {
+                chart
+                    .draw_series(std::iter::once(Circle::new(
+                        (get_axis(p, ix), get_axis(p, iy)),
+                        4,
+                        RED.filled(),
+                    )))
+                    .map_err(draw_err)?;
+            }
+            root.present().map_err(draw_err)?;
+        }
+        drop(root);
+        if config.verbose {
+            println!("wrote {}", output_path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Opacity for one wall projection: the per-plane override when given,
+/// otherwise `--projection-alpha`, clamped to the valid 0-1 range.
+fn projection_alpha(plane_override: Option<f64>, config: &Config) -> f64 {
+    plane_override
+        .unwrap_or(config.projection_alpha)
+        .clamp(0.0, 1.0)
+}
+
+/// Split the drawn trail into runs with no dropout between neighbours
+/// (per the `gap` predicate over absolute sample indices), so one broken
+/// `LineSeries` is drawn per run. A single run when `--gap-threshold` is
+/// off.
+fn gap_runs<'d, F: Fn(usize, usize) -> bool>(
+    drawn: &'d [(usize, Point3)],
+    gap: &F,
+) -> Vec<&'d [(usize, Point3)]> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..drawn.len() {
+        if gap(drawn[i - 1].0, drawn[i].0) {
+            runs.push(&drawn[start..i]);
+            start = i;
+        }
+    }
+    runs.push(&drawn[start..]);
+    runs
+}
+
+/// Where a plot-space point lands when flattened onto `plane`'s wall.
+/// Plane names are data planes: flattening data z lands on the floor by
+/// default but on the far wall under `--no-axis-swap`, and the data-xy
+/// plane the other way around. `None` for an unknown plane name.
+fn wall_point(scene: &Scene, plane: &str, p: Point3) -> Option<Point3> {
+    let config = scene.config;
+    match plane {
+        "xy" if !config.no_axis_swap => Some((p.0, scene.bounds.floor(), p.2)),
+        "xz" if config.no_axis_swap => Some((p.0, scene.bounds.floor(), p.2)),
+        "xy" | "xz" => Some((p.0, p.1, scene.bounds.z.1)),
+        "yz" => Some((scene.bounds.x.0, p.1, p.2)),
+        _ => None,
+    }
+}
+
+/// Position on the color scale of the trail segment starting at `sample`,
+/// under the active color-by mode; `None` when neither mode is enabled.
+fn segment_scalar(scene: &Scene, sample: usize) -> Option<f64> {
+    let config = scene.config;
+    if let Some(values) = scene.column_values {
+        let (lo, hi) = scene.column_range;
+        let v = values.get(sample).copied().unwrap_or(lo);
+        let v = if v.is_finite() { v } else { lo };
+        Some(((v - lo) / (hi - lo).max(f64::EPSILON)).clamp(0.0, 1.0))
+    } else if config.color_by_speed {
+        let (lo, hi) = scene.speed_range;
+        Some(((scene.speeds[sample] - lo) / (hi - lo)).clamp(0.0, 1.0))
+    } else if config.color_by_accel {
+        let (lo, hi) = scene.accel_range;
+        Some(((scene.accels[sample] - lo) / (hi - lo)).clamp(0.0, 1.0))
+    } else if config.color_by_time {
+        Some(sample as f64 / scene.xyz.len().max(1) as f64)
+    } else {
+        None
+    }
+}
+
+/// Draw the 3-D axes, applying `--x-ticks`/`--y-ticks`/`--z-ticks` and
+/// `--tick-precision` on top of plotters' defaults. The counts refer to
+/// the drawn chart axes, i.e. after the default data-y/data-z swap.
+fn draw_axes(
+    chart: &mut ChartContext<BitMapBackend, Cartesian3d<RangedCoordf64, RangedCoordf64, RangedCoordf64>>,
+    text_color: RGBColor,
+    config: &Config,
+) -> Result<(), TrajViewerError> {
+    let precision = config.tick_precision.unwrap_or(2);
+    let fmt = move |v: &f64| format!("{v:.precision$}");
+    let mut axes = chart.configure_axes();
+    axes.label_style(("sans-serif", 12).into_font().color(&text_color));
+    if let Some(n) = config.x_ticks {
+        axes.x_labels(n);
+    }
+    if let Some(n) = config.y_ticks {
+        axes.y_labels(n);
+    }
+    if let Some(n) = config.z_ticks {
+        axes.z_labels(n);
+    }
+    if config.tick_precision.is_some() {
+        axes.x_formatter(&fmt).y_formatter(&fmt).z_formatter(&fmt);
+    }
+    axes.draw().map_err(draw_err)
+}
+
+/// Draw `--floor-grid` reference lines on the floor plane, one line every
+/// `spacing` data units along each horizontal axis. Faint and drawn
+/// before the body, so they read as ground rather than trajectory.
+fn draw_floor_grid(
+    chart: &mut ChartContext<BitMapBackend, Cartesian3d<RangedCoordf64, RangedCoordf64, RangedCoordf64>>,
+    scene: &Scene,
+    spacing: f64,
+) -> Result<(), TrajViewerError> {
+    if spacing <= 0.0 {
+        return Err(TrajViewerError::InvalidConfig(
+            "--floor-grid spacing must be positive".into(),
+        ));
+    }
+    let theme = theme_colors(scene.config);
+    let floor = scene.bounds.floor();
+    let (x0, x1) = scene.bounds.x;
+    let (z0, z1) = scene.bounds.z;
+    let style = theme.foreground.mix(theme.grid_alpha);
+
+    let mut x = (x0 / spacing).ceil() * spacing;
+    while x <= x1 {
+        chart
+            .draw_series(LineSeries::new([(x, floor, z0), (x, floor, z1)], style))
+            .map_err(draw_err)?;
+        x += spacing;
+    }
+    let mut z = (z0 / spacing).ceil() * spacing;
+    while z <= z1 {
+        chart
+            .draw_series(LineSeries::new([(x0, floor, z), (x1, floor, z)], style))
+            .map_err(draw_err)?;
+        z += spacing;
+    }
+    Ok(())
+}
+
+fn draw_tube(
+    chart: &mut ChartContext<BitMapBackend, Cartesian3d<RangedCoordf64, RangedCoordf64, RangedCoordf64>>,
+    scene: &Scene,
+    drawn: &[(usize, Point3)],
+) -> Result<(), TrajViewerError> {
+    let half_width = 0.01 * (scene.bounds.x.1 - scene.bounds.x.0).abs().max(f64::EPSILON);
+    let (z0, z1) = scene.bounds.z;
+    let depth_span = (z1 - z0).abs().max(f64::EPSILON);
+
+    for w in drawn.windows(2) {
+        let (sample, a) = w[0];
+        let b = w[1].1;
+
+        let base = match segment_scalar(scene, sample) {
+            Some(v) => scalar_color(v, scene.config.colormap),
+            None => RGBColor(60, 60, 200),
+        };
+
+        // Darker the farther into the scene the segment midpoint sits.
+        let depth = (((a.2 + b.2) / 2.0 - z0) / depth_span).clamp(0.0, 1.0);
+        let shade = 1.0 - 0.6 * depth;
+        let color = RGBColor(
+            (base.0 as f64 * shade) as u8,
+            (base.1 as f64 * shade) as u8,
+            (base.2 as f64 * shade) as u8,
+        );
+
+        let quad = vec![
+            (a.0 - half_width, a.1, a.2),
+            (a.0 + half_width, a.1, a.2),
+            (b.0 + half_width, b.1, b.2),
+            (b.0 - half_width, b.1, b.2),
+        ];
+        chart
+            .draw_series(std::iter::once(Polygon::new(quad, color.filled())))
+            .map_err(draw_err)?;
+    }
+    Ok(())
+}
+
+/// Line segments forming an arrow from `from` to `tip`: the shaft plus two
+/// barbs angled back from the tip.
+fn arrow_segments(from: Point3, tip: Point3) -> Vec<Vec<Point3>> {
+    let dir = (tip.0 - from.0, tip.1 - from.1, tip.2 - from.2);
+    let len = (dir.0 * dir.0 + dir.1 * dir.1 + dir.2 * dir.2).sqrt();
+    if len <= f64::EPSILON {
+        return Vec::new();
+    }
+    let unit = (dir.0 / len, dir.1 / len, dir.2 / len);
+    // A perpendicular via cross product with the vertical (or x when the
+    // arrow itself is vertical).
+    let mut perp = (-unit.2, 0.0, unit.0);
+    let plen = (perp.0 * perp.0 + perp.2 * perp.2).sqrt();
+    if plen <= f64::EPSILON {
+        perp = (1.0, 0.0, 0.0);
+    } else {
+        perp = (perp.0 / plen, 0.0, perp.2 / plen);
+    }
+    let back = 0.2 * len;
+    let side = 0.1 * len;
+    let base = (
+        tip.0 - unit.0 * back,
+        tip.1 - unit.1 * back,
+        tip.2 - unit.2 * back,
+    );
+    vec![
+        vec![from, tip],
+        vec![
+            tip,
+            (base.0 + perp.0 * side, base.1, base.2 + perp.2 * side),
+        ],
+        vec![
+            tip,
+            (base.0 - perp.0 * side, base.1, base.2 - perp.2 * side),
+        ],
+    ]
+}
+
+/// The validated per-frame delay, in GIF centiseconds. GIF stores delays
+/// as u16 centiseconds, so `--secs` values off that grid are clamped with
+/// a warning: a delay that rounds to 0 makes most players substitute
+/// their own default speed, and anything over 655.35 s overflows the
+/// field.
+fn validated_delay_cs(config: &Config) -> u16 {
+    let cs = (config.secs * 100.0).round();
+    if cs < 1.0 {
+        eprintln!(
+            "warning: --secs {} rounds below one centisecond, the GIF minimum; using 0.01 s",
+            config.secs
+        );
+        1
+    } else if cs > f64::from(u16::MAX) {
+        eprintln!(
+            "warning: --secs {} exceeds 655.35 s, the largest GIF frame delay; clamping",
+            config.secs
+        );
+        u16::MAX
+    } else {
+        cs as u16
+    }
+}
+
+/// The oscillating camera yaw for a given output frame. Frame 0 sits
+/// exactly at `--start-yaw`, and `--start-delta-sign` picks the initial
+/// sweep direction, so two runs step through identical angles.
+fn yaw_at(frame_no: usize, config: &Config) -> f64 {
+    config.start_yaw + config.start_delta_sign.signum() * 0.5 * (frame_no as f64 * 0.05).sin()
+}
+
+/// Trail length in samples for the frame whose leading sample is `lead`,
+/// shrinking with speed when `--adaptive-trail` is set.
+fn trail_length(scene: &Scene, lead: usize) -> usize {
+    // `--mode accumulate`: the path grows from the first sample and never
+    // shrinks. `--trail-stride` still thins the polyline, which keeps the
+    // per-frame drawing cost manageable on long trajectories.
+    if scene.config.mode == Mode::Accumulate {
+        return lead.max(1);
+    }
+    // `--trail-seconds` wins over the sample-count modes: walk back until
+    // the window would span more than S time units, so irregular sampling
+    // still yields a temporally consistent trail.
+    if let Some(span) = scene.config.trail_seconds {
+        let t_lead = scene.ts.get(lead).copied().unwrap_or(0.0);
+        let mut from = lead;
+        while from > 0 && t_lead - scene.ts[from - 1] <= span {
+            from -= 1;
+        }
+        return (lead - from).max(1);
+    }
+    let base = scene.config.trail;
+    match scene.config.adaptive_trail {
+        Some(ref_speed) if ref_speed > 0.0 => {
+            let speed = scene.speeds.get(lead).copied().unwrap_or(ref_speed);
+            let scaled = base as f64 * ref_speed / speed.max(f64::EPSILON);
+            (scaled as usize).clamp(base / 4, base * 4).max(1)
+        }
+        _ => base,
+    }
+}
+
+/// Eight-anchor approximations of the perceptual colormaps, sampled from
+/// the reference implementations; intermediate values are interpolated.
+const VIRIDIS: [(u8, u8, u8); 8] = [
+    (68, 1, 84),
+    (70, 50, 127),
+    (54, 92, 141),
+    (39, 127, 143),
+    (31, 161, 135),
+    (74, 194, 109),
+    (159, 218, 58),
+    (253, 231, 37),
+];
+const PLASMA: [(u8, u8, u8); 8] = [
+    (13, 8, 135),
+    (84, 2, 163),
+    (139, 10, 165),
+    (185, 50, 137),
+    (219, 92, 104),
+    (244, 136, 73),
+    (254, 188, 43),
+    (240, 249, 33),
+];
+const TURBO: [(u8, u8, u8); 8] = [
+    (48, 18, 59),
+    (70, 107, 227),
+    (40, 187, 235),
+    (32, 240, 166),
+    (141, 252, 76),
+    (234, 220, 56),
+    (250, 125, 23),
+    (122, 4, 3),
+];
+
+/// Linear interpolation through a colormap's anchor colors.
+fn lookup(anchors: &[(u8, u8, u8)], v: f64) -> RGBColor {
+    let pos = v * (anchors.len() - 1) as f64;
+    let i = (pos as usize).min(anchors.len() - 2);
+    let f = pos - i as f64;
+    let (a, b) = (anchors[i], anchors[i + 1]);
+    let lerp = |a: u8, b: u8| (a as f64 + f * (b as f64 - a as f64)) as u8;
+    RGBColor(lerp(a.0, b.0), lerp(a.1, b.1), lerp(a.2, b.2))
+}
+
+/// Map a scalar in [0, 1] through the `--colormap` gradient; used by the
+/// color-by modes, the heatmap and the 3D density.
+pub fn scalar_color(v: f64, colormap: Colormap) -> RGBColor {
+    let v = v.clamp(0.0, 1.0);
+    match colormap {
+        Colormap::Viridis => lookup(&VIRIDIS, v),
+        Colormap::Plasma => lookup(&PLASMA, v),
+        Colormap::Turbo => lookup(&TURBO, v),
+        Colormap::Jet => {
+            // Classic piecewise ramp: blue, cyan, yellow, red.
+            let channel = |c: f64| (255.0 * (1.5 - (4.0 * v - c).abs()).clamp(0.0, 1.0)) as u8;
+            RGBColor(channel(3.0), channel(2.0), channel(1.0))
+        }
+        Colormap::Grayscale => {
+            let g = (255.0 * v) as u8;
+            RGBColor(g, g, g)
+        }
+    }
+}
+
+/// Vertical gradient legend on the right edge for the color-by modes.
+fn draw_colorbar(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    scene: &Scene,
+) -> Result<(), TrajViewerError> {
+    let (w, h) = root.dim_in_pixel();
+    let bar_x = w as i32 - 30;
+    let top = 60;
+    let bottom = h as i32 - 60;
+    let steps = (bottom - top).max(1);
+
+    for i in 0..steps {
+        let v = 1.0 - i as f64 / steps as f64;
+        root.draw(&Rectangle::new(
+            [(bar_x, top + i), (bar_x + 12, top + i + 1)],
+            scalar_color(v, scene.config.colormap).filled(),
+        ))
+        .map_err(draw_err)?;
+    }
+
+    let (lo, hi) = if scene.config.color_by_column.is_some() {
+        scene.column_range
+    } else if scene.config.color_by_speed {
+        scene.speed_range
+    } else if scene.config.color_by_accel {
+        scene.accel_range
+    } else {
+        (
+            scene.ts.first().copied().unwrap_or(0.0),
+            scene.ts.last().copied().unwrap_or(0.0),
+        )
+    };
+    let text_color = match &scene.config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme_colors(scene.config).foreground,
+    };
+    root.draw(&Text::new(
+        format!("{hi:.1}"),
+        (bar_x - 8, top - 16),
+        ("sans-serif", 12).into_font().color(&text_color),
+    ))
+    .map_err(draw_err)?;
+    root.draw(&Text::new(
+        format!("{lo:.1}"),
+        (bar_x - 8, bottom + 4),
+        ("sans-serif", 12).into_font().color(&text_color),
+    ))
+    .map_err(draw_err)?;
+    Ok(())
+}
+
+/// Plot-space bounds of the data `y` column: the depth axis by default,
+/// the vertical axis under `--no-axis-swap`.
+fn data_y_bounds(scene: &Scene) -> (f64, f64) {
+    if scene.config.no_axis_swap {
+        scene.bounds.y
+    } else {
+        scene.bounds.z
+    }
+}
+
+/// 2D occupancy histogram of the XY positions over the scene bounds, with
+/// `--bins` bins per axis. Bin `i` covers the half-open data range
+/// `[min + i*width, min + (i+1)*width)`; samples outside the bounds are
+/// clamped into the edge bins.
+struct Occupancy {
+    bins: usize,
+    x0: f64,
+    y0: f64,
+    dx: f64,
+    dy: f64,
+    /// Indexed as `counts[bx][by]`.
+    counts: Vec<Vec<u32>>,
+}
+
+impl Occupancy {
+    fn compute(scene: &Scene) -> Occupancy {
+        let bins = scene.config.bins.max(1);
+        let (x0, x1) = scene.bounds.x;
+        let (y0, y1) = data_y_bounds(scene);
+        let mut counts = vec![vec![0u32; bins]; bins];
+        // `* bins` with a `min` cap, not `* (bins - 1)`: bins must be
+        // `span / bins` wide to match the documented edges and the centers
+        // `write_csv` exports.
+        let bin_of = |v: f64, lo: f64, hi: f64| {
+            ((((v - lo) / (hi - lo)).clamp(0.0, 1.0) * bins as f64) as usize).min(bins - 1)
+        };
+        for p in scene.xyz {
+            let bx = bin_of(p.0, x0, x1);
+            let py = if scene.config.no_axis_swap { p.1 } else { p.2 };
+            let by = bin_of(py, y0, y1);
+            counts[bx][by] += 1;
+        }
+        Occupancy {
+            bins,
+            x0,
+            y0,
+            dx: (x1 - x0) / bins as f64,
+            dy: (y1 - y0) / bins as f64,
+            counts,
+        }
+    }
+
+    fn max_count(&self) -> u32 {
+        self.counts
+            .iter()
+            .flat_map(|row| row.iter())
+            .cloned()
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Write the histogram as `x_center,y_center,count` rows for
+    /// downstream analysis (`--occupancy-out`).
+    fn write_csv(&self, path: &Path) -> Result<(), TrajViewerError> {
+        let mut out = String::from("x_center,y_center,count\n");
+        for bx in 0..self.bins {
+            for by in 0..self.bins {
+                let x = self.x0 + (bx as f64 + 0.5) * self.dx;
+                let y = self.y0 + (by as f64 + 0.5) * self.dy;
+                out.push_str(&format!("{x},{y},{}\n", self.counts[bx][by]));
+            }
+        }
+        std::fs::write(path, out)?;
+        Ok(())
+    }
+}
+
+/// Render a static 2D occupancy heatmap of the XY positions.
+fn render_heatmap(scene: &Scene, started: Instant) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let output_path =
+        output_path_for(config, &format!("{}_heatmap.png", config.filekey), "png", 1)?;
+
+    let bins = config.bins.max(1);
+    let occupancy = Occupancy::compute(scene);
+    let (x0, x1) = scene.bounds.x;
+    let (y0, y1) = data_y_bounds(scene);
+    let counts = &occupancy.counts;
+    let max_count = occupancy.max_count();
+
+    let theme = theme_colors(config);
+    let text_color = match &config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme.foreground,
+    };
+    let root = BitMapBackend::new(&output_path, (config.width, config.height)).into_drawing_area();
+    root.fill(&theme.background).map_err(draw_err)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!("{} occupancy", config.filekey),
+            ("sans-serif", 30).into_font().color(&text_color),
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(40)
+        .build_cartesian_2d(x0..x1, y0..y1)
+        .map_err(draw_err)?;
+    chart
+        .configure_mesh()
+        .label_style(("sans-serif", 12).into_font().color(&text_color))
+        .axis_style(text_color)
+        .draw()
+        .map_err(draw_err)?;
+
+    let dx = (x1 - x0) / bins as f64;
+    let dy = (y1 - y0) / bins as f64;
+    chart
+        .draw_series((0..bins).flat_map(|bx| (0..bins).map(move |by| (bx, by))).map(
+            |(bx, by)| {
+                let v = counts[bx][by] as f64 / max_count as f64;
+                let x = x0 + bx as f64 * dx;
+                let y = y0 + by as f64 * dy;
+                Rectangle::new(
+                    [(x, y), (x + dx, y + dy)],
+                    scalar_color(v, config.colormap).mix(if v > 0.0 { 0.9 } else { 0.0 }).filled(),
+                )
+            },
+        ))
+        .map_err(draw_err)?;
+
+    if let Some(length) = config.scale_bar {
+        draw_scale_bar(&root, &chart, scene, length)?;
+    }
+
+    root.present().map_err(draw_err)?;
+    drop(chart);
+    drop(root);
+    Ok(RenderReport {
+        frames_written: 1,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Frames in the density3d rotation; the whole trajectory is in every
+/// frame, so only the camera animates.
+const DENSITY3D_FRAMES: usize = 60;
+
+/// Render the whole trajectory voxelized into a `--bins`-per-axis 3D grid,
+/// drawing occupied voxels as semi-transparent cubes colored by density,
+/// as a single rotating GIF (`--mode density3d`).
+fn render_density3d(scene: &Scene, started: Instant) -> Result<RenderReport, TrajViewerError> {
+    let config = scene.config;
+    let output_path = output_path_for(
+        config,
+        &format!("{}_density3d.gif", config.filekey),
+        "gif",
+        DENSITY3D_FRAMES,
+    )?;
+    let delay_ms = u32::from(validated_delay_cs(config)) * 10;
+
+    // Voxelize in plot space; samples outside the bounds are clamped into
+    // the edge voxels, matching the 2D occupancy histogram.
+    let bins = config.bins.max(1);
+    let b = scene.bounds;
+    let spans = [
+        (b.x.1 - b.x.0).max(f64::EPSILON),
+        (b.y.1 - b.y.0).max(f64::EPSILON),
+        (b.z.1 - b.z.0).max(f64::EPSILON),
+    ];
+    let mut counts = vec![0u32; bins * bins * bins];
+    for p in scene.xyz {
+        // `* bins` with a `min` cap, so assignment matches the
+        // `span / bins`-wide cubes `corner` draws and the 2D occupancy
+        // histogram's edges.
+        let bin = |v: f64, lo: f64, span: f64| {
+            ((((v - lo) / span).clamp(0.0, 1.0) * bins as f64) as usize).min(bins - 1)
+        };
+        let (bx, by, bz) = (
+            bin(p.0, b.x.0, spans[0]),
+            bin(p.1, b.y.0, spans[1]),
+            bin(p.2, b.z.0, spans[2]),
+        );
+        counts[(bx * bins + by) * bins + bz] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(1).max(1) as f64;
+
+    let root = BitMapBackend::gif(&output_path, (config.width, config.height), delay_ms)
+        .map_err(draw_err)?
+        .into_drawing_area();
+    let theme = theme_colors(config);
+    let text_color = match &config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme.foreground,
+    };
+
+    let mut progress = Progress::new();
+    let bar = progress.bar(DENSITY3D_FRAMES, "Rendering");
+    let mut frames_written = 0;
+    for frame_no in 0..DENSITY3D_FRAMES {
+        if cancelled() {
+            break;
+        }
+        root.fill(&theme.background).map_err(draw_err)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(
+                format!("{} 3D density", config.filekey),
+                ("sans-serif", 30).into_font().color(&text_color),
+            )
+            .build_cartesian_3d(b.x.0..b.x.1, b.y.0..b.y.1, b.z.0..b.z.1)
+            .map_err(draw_err)?;
+        let (pitch, yaw, scale) = if scene.keyframes.is_empty() {
+            (0.25, yaw_at(frame_no, config), config.view_scale)
+        } else {
+            camera_at(&scene.keyframes, frame_no)
+        };
+        chart.with_projection(|mut pb| {
+            pb.yaw = yaw;
+            pb.pitch = pitch;
+            pb.scale = scale;
+            pb.into_matrix()
+        });
+        draw_axes(&mut chart, text_color, config)?;
+
+        for bx in 0..bins {
+            for by in 0..bins {
+                for bz in 0..bins {
+                    let count = counts[(bx * bins + by) * bins + bz];
+                    if count == 0 {
+                        continue;
+                    }
+                    let v = count as f64 / max_count;
+                    let corner = |i: usize, lo: f64, span: f64| lo + i as f64 * span / bins as f64;
+                    let (x0, y0, z0) = (
+                        corner(bx, b.x.0, spans[0]),
+                        corner(by, b.y.0, spans[1]),
+                        corner(bz, b.z.0, spans[2]),
+                    );
+                    let (x1, y1, z1) = (
+                        corner(bx + 1, b.x.0, spans[0]),
+                        corner(by + 1, b.y.0, spans[1]),
+                        corner(bz + 1, b.z.0, spans[2]),
+                    );
+                    // Denser voxels are both warmer and more opaque, so
+                    // sparse ones stay see-through.
+                    let style = scalar_color(v, config.colormap).mix(0.15 + 0.6 * v).filled();
+                    chart
+                        .draw_series(std::iter::once(Cubiod::new(
+                            [(x0, y0, z0), (x1, y1, z1)],
+                            style,
+                            style,
+                        )))
+                        .map_err(draw_err)?;
+                }
+            }
+        }
+
+        root.present().map_err(draw_err)?;
+        frames_written += 1;
+        progress.inc_and_draw(&bar, 1);
+    }
+    drop(root);
+
+    Ok(RenderReport {
+        frames_written,
+        output_path,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// Draw a labeled physical scale reference (`--scale-bar`) as a thick line
+/// of the given data-unit length anchored in the configured corner.
+fn draw_scale_bar(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    chart: &ChartContext<BitMapBackend, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    scene: &Scene,
+    length: f64,
+) -> Result<(), TrajViewerError> {
+    if length <= 0.0 {
+        return Err(TrajViewerError::InvalidConfig(format!(
+            "--scale-bar length must be positive, got {length}"
+        )));
+    }
+
+    // Pixel length of `length` data units along x, from two mapped points.
+    let (x0, _) = scene.bounds.x;
+    let (y0, _) = data_y_bounds(scene);
+    let a = chart.backend_coord(&(x0, y0));
+    let b = chart.backend_coord(&(x0 + length, y0));
+    let px = (b.0 - a.0).abs();
+
+    let (w, h) = root.dim_in_pixel();
+    let margin = 30;
+    let (bar_x, bar_y) = match scene.config.scale_bar_pos {
+        Corner::TopLeft => (margin, margin),
+        Corner::TopRight => (w as i32 - margin - px, margin),
+        Corner::BottomLeft => (margin, h as i32 - margin),
+        Corner::BottomRight => (w as i32 - margin - px, h as i32 - margin),
+    };
+
+    root.draw(&PathElement::new(
+        vec![(bar_x, bar_y), (bar_x + px, bar_y)],
+        theme_colors(scene.config).foreground.stroke_width(4),
+    ))
+    .map_err(draw_err)?;
+    let label = match &scene.units {
+        Some(units) => format!("{length} {units}"),
+        None => format!("{length}"),
+    };
+    draw_text(root, &label, (bar_x, bar_y - 18), 14, scene.config)?;
+    Ok(())
+}
+
+/// Colors supplied by the `--theme` switch: the frame background, the
+/// default ink for body/axes/text, the per-plane projection colors and
+/// the floor-grid alpha. Explicit color flags override these per use.
+struct ThemeColors {
+    background: RGBColor,
+    foreground: RGBColor,
+    xy: RGBColor,
+    xz: RGBColor,
+    yz: RGBColor,
+    grid_alpha: f64,
+}
+
+fn theme_colors(config: &Config) -> ThemeColors {
+    match config.theme {
+        crate::config::Theme::Light => ThemeColors {
+            background: WHITE,
+            foreground: BLACK,
+            xy: BLUE,
+            xz: GREEN,
+            yz: MAGENTA,
+            grid_alpha: 0.15,
+        },
+        crate::config::Theme::Dark => ThemeColors {
+            background: RGBColor(16, 16, 20),
+            foreground: RGBColor(225, 225, 225),
+            xy: CYAN,
+            xz: RGBColor(120, 220, 120),
+            yz: RGBColor(240, 120, 240),
+            grid_alpha: 0.25,
+        },
+    }
+}
+
+/// Parse a `#rrggbb` (or `rrggbb`) color specification.
+fn parse_color(spec: &str) -> Result<RGBColor, TrajViewerError> {
+    let hex = spec.trim_start_matches('#');
+    if hex.len() == 6 {
+        if let Ok(value) = u32::from_str_radix(hex, 16) {
+            return Ok(RGBColor(
+                (value >> 16) as u8,
+                (value >> 8) as u8,
+                value as u8,
+            ));
+        }
+    }
+    Err(TrajViewerError::InvalidConfig(format!(
+        "expected a #rrggbb color, got `{spec}`"
+    )))
+}
+
+/// Draw the caption roughly bottom-center for `--caption-pos bottom`,
+/// centered by an average-glyph-width estimate since the backend cannot
+/// measure text here.
+fn draw_bottom_caption(
+    area: &DrawingArea<BitMapBackend, Shift>,
+    title: &str,
+    config: &Config,
+) -> Result<(), TrajViewerError> {
+    let (w, h) = area.dim_in_pixel();
+    let x = (w as i32 - title.len() as i32 * 15) / 2;
+    draw_text(area, title, (x.max(0), h as i32 - 40), 30, config)
+}
+
+/// Draw annotation text in the `--text-color`, with an optional halo in
+/// the `--text-outline` color (the text re-drawn at one-pixel offsets)
+/// so it stays legible over any background.
+fn draw_text(
+    root: &DrawingArea<BitMapBackend, Shift>,
+    text: &str,
+    pos: (i32, i32),
+    size: u32,
+    config: &Config,
+) -> Result<(), TrajViewerError> {
+    let color = match &config.text_color {
+        Some(spec) => parse_color(spec)?,
+        None => theme_colors(config).foreground,
+    };
+    if let Some(spec) = &config.text_outline {
+        let outline = parse_color(spec)?;
+        for (dx, dy) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            root.draw(&Text::new(
+                text.to_string(),
+                (pos.0 + dx, pos.1 + dy),
+                ("sans-serif", size).into_font().color(&outline),
+            ))
+            .map_err(draw_err)?;
+        }
+    }
+    root.draw(&Text::new(
+        text.to_string(),
+        pos,
+        ("sans-serif", size).into_font().color(&color),
+    ))
+    .map_err(draw_err)?;
+    Ok(())
+}
+
+/// Create the directory a report/manifest file will be written into,
+/// with a clearer error than the raw `std::fs::write` failure.
+pub fn ensure_parent_dir(path: &Path) -> Result<(), TrajViewerError> {
+    let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) else {
+        return Ok(());
+    };
+    std::fs::create_dir_all(parent).map_err(|e| {
+        TrajViewerError::InvalidConfig(format!(
+            "cannot create directory `{}`: {e}",
+            parent.display()
+        ))
+    })
+}
+
+pub(crate) fn draw_err<E: std::fmt::Display>(e: E) -> TrajViewerError {
+    TrajViewerError::Drawing(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_keyframes_interpolate() {
+        let kfs = parse_keyframes("0,0.2,1.0,0.8; 10,0.4,2.0,1.0").unwrap();
+        assert_eq!(kfs.len(), 2);
+        assert_eq!(camera_at(&kfs, 0), (0.2, 1.0, 0.8));
+        let (pitch, yaw, scale) = camera_at(&kfs, 5);
+        assert!((pitch - 0.3).abs() < 1e-9);
+        assert!((yaw - 1.5).abs() < 1e-9);
+        assert!((scale - 0.9).abs() < 1e-9);
+        // Clamped past the last keyframe.
+        assert_eq!(camera_at(&kfs, 99), (0.4, 2.0, 1.0));
+        assert!(parse_keyframes("1,2,3").is_err());
+    }
+
+    /// Guards against rendering regressions plotters or geometry changes
+    /// would introduce: a fixed demo trajectory at a fixed camera must match
+    /// the checked-in reference within a small per-pixel tolerance. Run with
+    /// `BLESS=1` to regenerate the reference after an intentional change.
+    #[test]
+    fn golden_frame_matches_reference() {
+        use clap::Parser;
+
+        let config = Config::parse_from([
+            "traj_viewer",
+            "golden",
+            "--width",
+            "320",
+            "--height",
+            "240",
+            "--camera-keyframes",
+            "0,0.25,1.0,0.8",
+        ]);
+        let df = crate::loader::demo_trajectory(1).unwrap();
+        let main = TrajData::new("golden".into(), &df, &config).unwrap();
+        let scene = build_scene(&main, &[], &config).unwrap();
+
+        let (w, h) = (config.width, config.height);
+        let mut buf = vec![0u8; (w * h * 3) as usize];
+        {
+            let root = BitMapBackend::with_buffer(&mut buf, (w, h)).into_drawing_area();
+            draw_frame(&root, &scene, 500, 0).unwrap();
+            root.present().unwrap();
+        }
+
+        let reference_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden_frame.png");
+        if std::env::var("BLESS").as_deref() == Ok("1") {
+            std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+            image::save_buffer(&reference_path, &buf, w, h, image::ColorType::Rgb8).unwrap();
+            return;
+        }
+
+        let reference = image::open(&reference_path)
+            .expect("missing tests/golden_frame.png; regenerate with BLESS=1")
+            .to_rgb8();
+        assert_eq!(reference.dimensions(), (w, h));
+        let differing = buf
+            .iter()
+            .zip(reference.as_raw())
+            .filter(|(a, b)| (**a as i16 - **b as i16).abs() > 8)
+            .count();
+        let fraction = differing as f64 / buf.len() as f64;
+        assert!(
+            fraction < 0.01,
+            "golden frame differs in {:.2}% of channel values",
+            fraction * 100.0
+        );
+    }
+
+    #[test]
+    fn delay_is_clamped_to_gif_range() {
+        use clap::Parser;
+
+        let config = Config::parse_from(["traj_viewer", "t", "--secs", "0.05"]);
+        assert_eq!(validated_delay_cs(&config), 5);
+        let config = Config::parse_from(["traj_viewer", "t", "--secs", "0.001"]);
+        assert_eq!(validated_delay_cs(&config), 1);
+        let config = Config::parse_from(["traj_viewer", "t", "--secs", "1000"]);
+        assert_eq!(validated_delay_cs(&config), u16::MAX);
+    }
+
+    #[test]
+    fn frame_hook_sees_every_frame() {
+        use clap::Parser;
+
+        let dir = std::env::temp_dir().join(format!("traj_viewer_hook_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = Config::parse_from([
+            "traj_viewer",
+            "hooked",
+            "--width",
+            "80",
+            "--height",
+            "60",
+            "--skip",
+            "400",
+            "--output-dir",
+            dir.to_str().unwrap(),
+        ]);
+        let df = crate::loader::demo_trajectory(1).unwrap();
+
+        let mut seen = Vec::new();
+        let report = render_trajectory_with_hook(&df, &config, |img, ctx| {
+            img.put_pixel(0, 0, image::Rgb([255, 0, 0]));
+            seen.push((ctx.frame_no, ctx.t0));
+        })
+        .unwrap();
+
+        assert!(!seen.is_empty());
+        assert_eq!(seen[0].0, 0);
+        assert!(seen.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!(report.output_path.exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parse_grid_accepts_rxc() {
+        assert!(matches!(parse_grid("2x3"), Ok((2, 3))));
+        assert!(matches!(parse_grid("1X1"), Ok((1, 1))));
+        assert!(parse_grid("0x2").is_err());
+        assert!(parse_grid("2").is_err());
+        assert!(parse_grid("axb").is_err());
+    }
+}